@@ -0,0 +1,21 @@
+use std::hint::black_box;
+
+use bevy_multiplayer_chess::{board::Board, fen::STARTING_FEN, perft::perft};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+fn bench_perft(c: &mut Criterion) {
+    let start = Board::from_fen(STARTING_FEN).unwrap();
+    let kiwipete = Board::from_fen(KIWIPETE).unwrap();
+
+    c.bench_function("perft 3 start position", |b| {
+        b.iter(|| perft(black_box(&start), 3))
+    });
+    c.bench_function("perft 2 kiwipete", |b| {
+        b.iter(|| perft(black_box(&kiwipete), 2))
+    });
+}
+
+criterion_group!(benches, bench_perft);
+criterion_main!(benches);
@@ -0,0 +1,326 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{Board, RedrawBoard},
+    history::MoveHistory,
+    network::NetworkConnection,
+    piece::{LocalPlayer, PieceColor},
+    protocol::Message,
+    rules::takeback_plies,
+    side_panel::{setup_side_panel, SidePanel},
+    state::{DrawReason, GameResult, GameState, WinReason},
+    FONT_SIZE,
+};
+
+pub struct ActionsPlugin;
+
+/// Something a player asks for besides playing a move.
+#[derive(Event, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GameAction {
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    ClaimDraw,
+    RequestTakeback,
+    AcceptTakeback,
+    DeclineTakeback,
+}
+
+/// Draw offer and takeback request waiting for an answer, and who made
+/// them. Both lapse once a move is played.
+#[derive(Resource, Debug, Default)]
+pub struct PendingOffers {
+    pub draw: Option<PieceColor>,
+    pub takeback: Option<PieceColor>,
+}
+
+#[derive(Component)]
+struct ActionBar;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingOffers>()
+            .add_event::<GameAction>()
+            .add_systems(Startup, setup_action_bar.after(setup_side_panel))
+            .add_systems(OnEnter(GameState::GameInitResources), clear_offers)
+            .add_systems(
+                Update,
+                (
+                    clear_offers.run_if(resource_exists_and_changed::<MoveHistory>),
+                    handle_action_buttons,
+                    apply_local_actions.run_if(not(resource_exists::<NetworkConnection>)),
+                    send_actions.run_if(resource_exists::<NetworkConnection>),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                update_action_bar.run_if(
+                    resource_changed::<PendingOffers>
+                        .or_else(state_changed::<GameState>)
+                        .or_else(resource_exists_and_changed::<Board>),
+                ),
+            );
+    }
+}
+
+impl GameAction {
+    fn label(&self) -> &'static str {
+        match self {
+            GameAction::Resign => "Resign",
+            GameAction::OfferDraw => "Offer draw",
+            GameAction::AcceptDraw => "Accept draw",
+            GameAction::DeclineDraw | GameAction::DeclineTakeback => "Decline",
+            GameAction::ClaimDraw => "Claim draw",
+            GameAction::RequestTakeback => "Takeback",
+            GameAction::AcceptTakeback => "Grant takeback",
+        }
+    }
+}
+
+/// Undoes the last `plies` moves of the game.
+pub fn take_back(
+    plies: usize,
+    board: &mut Board,
+    history: &mut MoveHistory,
+    redraw_events: &mut EventWriter<RedrawBoard>,
+) {
+    for _ in 0..plies.min(history.moves.len()) {
+        board.unmake_move();
+        history.moves.pop();
+    }
+    redraw_events.send(RedrawBoard);
+}
+
+fn setup_action_bar(mut commands: Commands, panel_query: Query<Entity, With<SidePanel>>) {
+    let Ok(panel) = panel_query.get_single() else {
+        return;
+    };
+
+    commands.entity(panel).with_children(|parent| {
+        parent.spawn((
+            NodeBundle {
+                style: Style {
+                    flex_wrap: FlexWrap::Wrap,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(8.0),
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                ..default()
+            },
+            ActionBar,
+        ));
+    });
+}
+
+fn clear_offers(mut offers: ResMut<PendingOffers>) {
+    if offers.draw.is_some() || offers.takeback.is_some() {
+        *offers = PendingOffers::default();
+    }
+}
+
+fn handle_action_buttons(
+    button_query: Query<(&Interaction, &GameAction), Changed<Interaction>>,
+    mut actions: EventWriter<GameAction>,
+) {
+    for (interaction, &action) in button_query.iter() {
+        if *interaction == Interaction::Pressed {
+            actions.send(action);
+        }
+    }
+}
+
+/// Plays out the actions of a game on this computer. Resignations and offers
+/// are made by the local player, the side to move when it plays both, and
+/// takebacks asked for by the side that just moved if it is theirs, the other
+/// side answering.
+fn apply_local_actions(
+    mut actions: EventReader<GameAction>,
+    mut offers: ResMut<PendingOffers>,
+    mut board: ResMut<Board>,
+    mut history: ResMut<MoveHistory>,
+    local_player: Res<LocalPlayer>,
+    mut redraw_events: EventWriter<RedrawBoard>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for &action in actions.read() {
+        let side_to_move = board.side_to_move;
+        let player = if local_player.controls(side_to_move) {
+            side_to_move
+        } else {
+            side_to_move.opponent()
+        };
+        let last_mover = if local_player.controls(side_to_move.opponent()) {
+            side_to_move.opponent()
+        } else {
+            side_to_move
+        };
+
+        match action {
+            GameAction::Resign => next_state.set(GameState::GameOver(GameResult::win(
+                player.opponent(),
+                WinReason::Resignation,
+            ))),
+            GameAction::OfferDraw => offers.draw = Some(player),
+            GameAction::AcceptDraw if offers.draw.is_some() => {
+                next_state.set(GameState::GameOver(GameResult::Draw(DrawReason::Agreement)));
+            }
+            GameAction::ClaimDraw => {
+                if let Some(reason) = board.claimable_draw() {
+                    next_state.set(GameState::GameOver(GameResult::Draw(reason)));
+                }
+            }
+            GameAction::RequestTakeback if !history.moves.is_empty() => {
+                offers.takeback = Some(last_mover);
+            }
+            GameAction::AcceptTakeback => {
+                let Some(color) = offers.takeback.take() else {
+                    continue;
+                };
+                if let Some(plies) = takeback_plies(&board, &history, color) {
+                    take_back(plies, &mut board, &mut history, &mut redraw_events);
+                }
+            }
+            GameAction::DeclineDraw => offers.draw = None,
+            GameAction::DeclineTakeback => offers.takeback = None,
+            GameAction::AcceptDraw | GameAction::RequestTakeback => {}
+        }
+    }
+}
+
+/// Passes the actions of the local player on to the server, which answers
+/// for the game.
+fn send_actions(
+    mut actions: EventReader<GameAction>,
+    mut offers: ResMut<PendingOffers>,
+    mut connection: ResMut<NetworkConnection>,
+) {
+    for &action in actions.read() {
+        let Some(color) = connection.color else {
+            continue;
+        };
+
+        let message = match action {
+            GameAction::Resign => Message::Resign,
+            GameAction::OfferDraw => {
+                offers.draw = Some(color);
+                Message::DrawOffer
+            }
+            GameAction::AcceptDraw => Message::DrawOffer,
+            GameAction::ClaimDraw => Message::ClaimDraw,
+            GameAction::DeclineDraw => {
+                offers.draw = None;
+                Message::DeclineDraw
+            }
+            GameAction::RequestTakeback => {
+                offers.takeback = Some(color);
+                Message::Takeback
+            }
+            GameAction::AcceptTakeback => Message::Takeback,
+            GameAction::DeclineTakeback => {
+                offers.takeback = None;
+                Message::DeclineTakeback
+            }
+        };
+        connection.send(&message);
+    }
+}
+
+fn spawn_button(parent: &mut ChildBuilder, action: GameAction) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: Color::srgb(0.25, 0.25, 0.3).into(),
+                ..default()
+            },
+            action,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                action.label(),
+                TextStyle {
+                    font_size: FONT_SIZE,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn spawn_note(parent: &mut ChildBuilder, text: &str, color: Color) {
+    parent.spawn(TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size: FONT_SIZE,
+            color,
+            ..default()
+        },
+    ));
+}
+
+/// Lays out the actions open to the local player: answering an offer from
+/// the other side, or resigning and making offers of their own.
+fn update_action_bar(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    offers: Res<PendingOffers>,
+    board: Option<Res<Board>>,
+    local_player: Res<LocalPlayer>,
+    bar_query: Query<Entity, With<ActionBar>>,
+) {
+    let Ok(bar) = bar_query.get_single() else {
+        return;
+    };
+    commands.entity(bar).despawn_descendants();
+
+    // Spectators and finished games have nothing to act on.
+    if *state.get() != GameState::InGame || !(local_player.white || local_player.black) {
+        return;
+    }
+
+    commands.entity(bar).with_children(|parent| {
+        let prompt = Color::srgb(0.8, 0.8, 0.6);
+
+        if let Some(color) = offers
+            .draw
+            .filter(|color| local_player.controls(color.opponent()))
+        {
+            spawn_note(parent, &format!("{} offers a draw", color), prompt);
+            spawn_button(parent, GameAction::AcceptDraw);
+            spawn_button(parent, GameAction::DeclineDraw);
+            return;
+        }
+        if let Some(color) = offers
+            .takeback
+            .filter(|color| local_player.controls(color.opponent()))
+        {
+            spawn_note(parent, &format!("{} asks for a takeback", color), prompt);
+            spawn_button(parent, GameAction::AcceptTakeback);
+            spawn_button(parent, GameAction::DeclineTakeback);
+            return;
+        }
+
+        spawn_button(parent, GameAction::Resign);
+        let can_claim = board.as_ref().is_some_and(|board| {
+            local_player.controls(board.side_to_move) && board.claimable_draw().is_some()
+        });
+        if can_claim {
+            spawn_button(parent, GameAction::ClaimDraw);
+        }
+        match offers.draw {
+            Some(_) => spawn_note(parent, "Draw offered", Color::srgb(0.6, 0.6, 0.6)),
+            None => spawn_button(parent, GameAction::OfferDraw),
+        }
+        match offers.takeback {
+            Some(_) => spawn_note(parent, "Takeback asked", Color::srgb(0.6, 0.6, 0.6)),
+            None => spawn_button(parent, GameAction::RequestTakeback),
+        }
+    });
+}
@@ -0,0 +1,355 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    actions::{GameAction, PendingOffers},
+    board::Board,
+    clock::GameClock,
+    engine::{Engine, SearchLimits, SearchResult},
+    history::MoveHistory,
+    network::NetworkConnection,
+    piece::{LocalPlayer, MoveRequest, MoveSet, PieceColor},
+    state::GameState,
+};
+
+pub struct AiPlugin;
+
+/// The side played by the built-in engine in a single-player game, and how
+/// it plays.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ComputerOpponent {
+    pub color: PieceColor,
+    pub difficulty: Difficulty,
+    pub style: PlayingStyle,
+}
+
+/// Strength of the engine: how deep and how long it searches, and how often
+/// it throws the result away for a random move.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difficulty {
+    pub name: &'static str,
+    /// Rating shown next to the name to give an idea of the strength. It is
+    /// a guess which has not been measured against rated players.
+    pub elo: u32,
+    pub limits: SearchLimits,
+    /// Chance of playing a random legal move instead of the best one found.
+    pub blunder_chance: f64,
+}
+
+/// How the engine goes about a game, whatever its strength.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum PlayingStyle {
+    /// Happy to draw, and to accept a draw offer in an even position.
+    Solid,
+    #[default]
+    Balanced,
+    /// Avoids draws and only agrees to one when clearly losing.
+    Aggressive,
+}
+
+/// Engine between two searches, kept so its transposition table carries over
+/// from one move to the next, and what it thought of its last move.
+#[derive(Resource, Default)]
+struct EngineState {
+    engine: Option<Engine>,
+    last_score: Option<i32>,
+}
+
+/// Search running in the background for the position after `plies` moves.
+/// Setting `stop` makes it return early.
+#[derive(Resource)]
+struct PendingSearch {
+    task: Task<(Engine, SearchResult)>,
+    plies: usize,
+    stop: Arc<AtomicBool>,
+}
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EngineState>()
+            .add_systems(
+                OnEnter(GameState::GameInitResources),
+                setup_computer_opponent,
+            )
+            .add_systems(
+                Update,
+                (
+                    cancel_search
+                        .before(poll_search)
+                        .run_if(resource_exists::<PendingSearch>),
+                    (
+                        poll_search
+                            .in_set(MoveSet::Input)
+                            .run_if(resource_exists::<PendingSearch>),
+                        start_search
+                            .after(MoveSet::Apply)
+                            .run_if(not(resource_exists::<PendingSearch>)),
+                        answer_offers.run_if(resource_changed::<PendingOffers>),
+                    )
+                        .run_if(
+                            in_state(GameState::InGame)
+                                .and_then(resource_exists::<ComputerOpponent>)
+                                .and_then(not(resource_exists::<NetworkConnection>)),
+                        ),
+                ),
+            );
+    }
+}
+
+impl Difficulty {
+    /// Levels offered in the main menu, weakest first.
+    pub const PRESETS: [Difficulty; 5] = [
+        Difficulty::new("Beginner", 800, 1, 100, 0.25),
+        Difficulty::new("Casual", 1100, 2, 250, 0.12),
+        Difficulty::new("Club", 1500, 3, 500, 0.05),
+        Difficulty::new("Strong", 1900, 5, 1000, 0.01),
+        Difficulty {
+            name: "Expert",
+            elo: 2200,
+            limits: SearchLimits {
+                depth: None,
+                time: Some(Duration::from_secs(3)),
+            },
+            blunder_chance: 0.0,
+        },
+    ];
+
+    /// Index in `PRESETS` of the level played unless another one is picked.
+    pub const DEFAULT_PRESET: usize = 2;
+
+    const fn new(
+        name: &'static str,
+        elo: u32,
+        depth: u32,
+        millis: u64,
+        blunder_chance: f64,
+    ) -> Self {
+        Self {
+            name,
+            elo,
+            limits: SearchLimits {
+                depth: Some(depth),
+                time: Some(Duration::from_millis(millis)),
+            },
+            blunder_chance,
+        }
+    }
+}
+
+/// Computer playing Black at the default level and style.
+impl Default for ComputerOpponent {
+    fn default() -> Self {
+        Self {
+            color: PieceColor::Black,
+            difficulty: Difficulty::PRESETS[Difficulty::DEFAULT_PRESET],
+            style: PlayingStyle::default(),
+        }
+    }
+}
+
+impl PlayingStyle {
+    pub fn next(self) -> Self {
+        match self {
+            PlayingStyle::Solid => PlayingStyle::Balanced,
+            PlayingStyle::Balanced => PlayingStyle::Aggressive,
+            PlayingStyle::Aggressive => PlayingStyle::Solid,
+        }
+    }
+
+    /// Contempt the engine searches with, see `Engine::contempt`.
+    fn contempt(self) -> i32 {
+        match self {
+            PlayingStyle::Solid => -25,
+            PlayingStyle::Balanced => 0,
+            PlayingStyle::Aggressive => 50,
+        }
+    }
+
+    /// Highest score of its own at which the engine agrees to a draw.
+    fn accept_draw_score(self) -> i32 {
+        match self {
+            PlayingStyle::Solid => 0,
+            PlayingStyle::Balanced => -100,
+            PlayingStyle::Aggressive => -300,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (about {} Elo)", self.name, self.elo)
+    }
+}
+
+impl fmt::Display for PlayingStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayingStyle::Solid => write!(f, "Solid"),
+            PlayingStyle::Balanced => write!(f, "Balanced"),
+            PlayingStyle::Aggressive => write!(f, "Aggressive"),
+        }
+    }
+}
+
+fn setup_computer_opponent(
+    computer: Option<Res<ComputerOpponent>>,
+    mut local_player: ResMut<LocalPlayer>,
+    mut engine_state: ResMut<EngineState>,
+) {
+    *engine_state = EngineState::default();
+
+    if let Some(computer) = computer {
+        *local_player = LocalPlayer::only(computer.color.opponent());
+    }
+}
+
+/// Time the engine may spend on its next move: its share of what is left on
+/// its clock, if the game has one, within its own limit.
+fn move_time(
+    limits: SearchLimits,
+    clock: Option<&GameClock>,
+    color: PieceColor,
+) -> Option<Duration> {
+    let Some(clock) = clock else {
+        return limits.time;
+    };
+
+    let bonus = Duration::from_secs(clock.clock.time_control().bonus_secs as u64);
+    let budget = clock.clock.remaining(color) / 30 + bonus * 3 / 4;
+    Some(limits.time.map_or(budget, |time| time.min(budget)))
+}
+
+/// Starts the engine thinking on a copy of the board once it is its turn,
+/// leaving the frame loop free while it searches.
+fn start_search(
+    mut commands: Commands,
+    board: Res<Board>,
+    history: Res<MoveHistory>,
+    computer: Res<ComputerOpponent>,
+    clock: Option<Res<GameClock>>,
+    mut engine_state: ResMut<EngineState>,
+) {
+    if board.side_to_move != computer.color {
+        return;
+    }
+
+    let limits = computer.difficulty.limits;
+    let limits = SearchLimits {
+        time: move_time(limits, clock.as_deref(), computer.color),
+        ..limits
+    };
+    let mut engine = engine_state.engine.take().unwrap_or_default();
+    engine.contempt = computer.style.contempt();
+    let board = board.clone();
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let task = AsyncComputeTaskPool::get().spawn({
+        let stop = stop.clone();
+        async move {
+            let result = engine.search(&board, limits, &stop);
+            (engine, result)
+        }
+    });
+
+    commands.insert_resource(PendingSearch {
+        task,
+        plies: history.moves.len(),
+        stop,
+    });
+}
+
+/// Plays the engine's move once its search is done, or now and then a random
+/// one at the easier levels.
+fn poll_search(
+    mut commands: Commands,
+    mut pending: ResMut<PendingSearch>,
+    board: Res<Board>,
+    history: Res<MoveHistory>,
+    computer: Res<ComputerOpponent>,
+    mut engine_state: ResMut<EngineState>,
+    mut move_requests: EventWriter<MoveRequest>,
+) {
+    let Some((engine, result)) = block_on(future::poll_once(&mut pending.task)) else {
+        return;
+    };
+    commands.remove_resource::<PendingSearch>();
+
+    engine_state.engine = Some(engine);
+    engine_state.last_score = Some(result.score);
+
+    if history.moves.len() != pending.plies {
+        return;
+    }
+    let Some(best_move) = result.best_move else {
+        return;
+    };
+    info!(
+        "Engine finds {} (depth {}, score {}, {} nodes)",
+        best_move, result.depth, result.score, result.nodes
+    );
+
+    let mut rng = rand::thread_rng();
+    let mut mv = best_move;
+    if rng.gen_bool(computer.difficulty.blunder_chance) {
+        if let Some(&random_move) = board.legal_moves(board.side_to_move).choose(&mut rng) {
+            info!("Engine plays {} instead", random_move);
+            mv = random_move;
+        }
+    }
+    move_requests.send(MoveRequest(mv));
+}
+
+/// Stops a search whose position is gone, because a move was taken back or
+/// the game has ended. A stopped search returns within a few thousand nodes,
+/// so it is waited for to get the engine and its transposition table back.
+fn cancel_search(
+    mut commands: Commands,
+    mut pending: ResMut<PendingSearch>,
+    history: Option<Res<MoveHistory>>,
+    state: Res<State<GameState>>,
+    mut engine_state: ResMut<EngineState>,
+) {
+    let outdated = history.map_or(true, |history| history.moves.len() != pending.plies);
+    if outdated || *state.get() != GameState::InGame {
+        pending.stop.store(true, Ordering::Relaxed);
+        let (engine, _) = block_on(&mut pending.task);
+        engine_state.engine = Some(engine);
+        commands.remove_resource::<PendingSearch>();
+    }
+}
+
+/// Answers the player's offers: takebacks are always granted, draws only
+/// accepted when the engine thinks it is worse off than its style allows.
+fn answer_offers(
+    offers: Res<PendingOffers>,
+    computer: Res<ComputerOpponent>,
+    engine_state: Res<EngineState>,
+    mut actions: EventWriter<GameAction>,
+) {
+    if offers.takeback.is_some_and(|color| color != computer.color) {
+        actions.send(GameAction::AcceptTakeback);
+    }
+
+    if offers.draw.is_some_and(|color| color != computer.color) {
+        let worse_off = engine_state
+            .last_score
+            .is_some_and(|score| score <= computer.style.accept_draw_score());
+        actions.send(if worse_off {
+            GameAction::AcceptDraw
+        } else {
+            GameAction::DeclineDraw
+        });
+    }
+}
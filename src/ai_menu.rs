@@ -0,0 +1,252 @@
+use bevy::prelude::*;
+
+use crate::{
+    ai::{ComputerOpponent, Difficulty, PlayingStyle},
+    network::NetworkConnection,
+    piece::PieceColor,
+    state::GameState,
+    BG_COLOR, FONT_SIZE, SIDE_PANEL_WIDTH,
+};
+
+pub struct AiMenuPlugin;
+
+/// Game against the computer the player is about to start, `difficulty`
+/// indexing `Difficulty::PRESETS`.
+#[derive(Resource, Debug)]
+struct AiMenuForm {
+    difficulty: usize,
+    style: PlayingStyle,
+    /// Side the player takes, the computer playing the other.
+    color: PieceColor,
+}
+
+#[derive(Component)]
+struct AiMenuRoot;
+
+#[derive(Component)]
+struct DifficultyDetails;
+
+#[derive(Component, Clone, Copy)]
+enum AiMenuButton {
+    Difficulty,
+    Style,
+    Color,
+    Start,
+    TwoPlayers,
+}
+
+impl Plugin for AiMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::MainMenu),
+            (setup_ai_menu, update_form_labels)
+                .chain()
+                .run_if(not(resource_exists::<NetworkConnection>)),
+        )
+        .add_systems(OnExit(GameState::MainMenu), despawn_ai_menu)
+        .add_systems(
+            Update,
+            (
+                handle_ai_menu_buttons,
+                update_form_labels.run_if(resource_changed::<AiMenuForm>),
+            )
+                .chain()
+                .run_if(in_state(GameState::MainMenu).and_then(resource_exists::<AiMenuForm>)),
+        );
+    }
+}
+
+fn text_style() -> TextStyle {
+    TextStyle {
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+        ..default()
+    }
+}
+
+fn spawn_button(parent: &mut ChildBuilder, label: impl Into<String>, button: AiMenuButton) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(10.0), Val::Px(6.0)),
+                    ..default()
+                },
+                background_color: Color::srgb(0.25, 0.25, 0.3).into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(label, text_style()));
+        });
+}
+
+fn setup_ai_menu(mut commands: Commands) {
+    commands.insert_resource(AiMenuForm {
+        difficulty: Difficulty::DEFAULT_PRESET,
+        style: PlayingStyle::default(),
+        color: PieceColor::White,
+    });
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    bottom: Val::Px(0.0),
+                    right: Val::Px(SIDE_PANEL_WIDTH),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(24.0)),
+                    row_gap: Val::Px(12.0),
+                    ..default()
+                },
+                background_color: Color::srgb_u8(BG_COLOR.0, BG_COLOR.1, BG_COLOR.2).into(),
+                ..default()
+            },
+            AiMenuRoot,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Play the computer",
+                TextStyle {
+                    font_size: 40.0,
+                    ..text_style()
+                },
+            ));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(8.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_button(parent, "", AiMenuButton::Difficulty);
+                    spawn_button(parent, "", AiMenuButton::Style);
+                    spawn_button(parent, "", AiMenuButton::Color);
+                });
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        color: Color::srgb(0.6, 0.6, 0.6),
+                        ..text_style()
+                    },
+                ),
+                DifficultyDetails,
+            ));
+
+            parent
+                .spawn(NodeBundle::default())
+                .with_children(|parent| spawn_button(parent, "Start game", AiMenuButton::Start));
+
+            parent.spawn(TextBundle::from_section(
+                "Play a friend",
+                TextStyle {
+                    font_size: 40.0,
+                    ..text_style()
+                },
+            ));
+
+            parent.spawn(NodeBundle::default()).with_children(|parent| {
+                spawn_button(parent, "Two players, one board", AiMenuButton::TwoPlayers)
+            });
+        });
+}
+
+fn despawn_ai_menu(mut commands: Commands, root_query: Query<Entity, With<AiMenuRoot>>) {
+    for entity in root_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn handle_ai_menu_buttons(
+    mut commands: Commands,
+    button_query: Query<(&Interaction, &AiMenuButton), Changed<Interaction>>,
+    mut form: ResMut<AiMenuForm>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, &button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            AiMenuButton::Difficulty => {
+                form.difficulty = (form.difficulty + 1) % Difficulty::PRESETS.len();
+            }
+            AiMenuButton::Style => form.style = form.style.next(),
+            AiMenuButton::Color => form.color = form.color.opponent(),
+            AiMenuButton::Start => {
+                commands.insert_resource(ComputerOpponent {
+                    color: form.color.opponent(),
+                    difficulty: Difficulty::PRESETS[form.difficulty],
+                    style: form.style,
+                });
+                next_state.set(GameState::GameInitResources);
+            }
+            AiMenuButton::TwoPlayers => {
+                commands.remove_resource::<ComputerOpponent>();
+                next_state.set(GameState::GameInitResources);
+            }
+        }
+    }
+}
+
+/// Describes the chosen level the way a player would size it up: how far
+/// ahead it looks and how often it blunders.
+fn describe(difficulty: &Difficulty) -> String {
+    let depth = match difficulty.limits.depth {
+        Some(depth) => format!("{} plies deep", depth),
+        None => "as deep as it can".to_string(),
+    };
+    let time = difficulty.limits.time.map_or(String::new(), |time| {
+        format!(" in {:.1}s", time.as_secs_f32())
+    });
+    let blunders = if difficulty.blunder_chance > 0.0 {
+        format!(
+            ", blundering on {:.0}% of moves",
+            difficulty.blunder_chance * 100.0
+        )
+    } else {
+        String::new()
+    };
+
+    format!("Looks {}{}{}", depth, time, blunders)
+}
+
+fn update_form_labels(
+    form: Res<AiMenuForm>,
+    button_query: Query<(&AiMenuButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+    details_query: Query<Entity, With<DifficultyDetails>>,
+) {
+    let difficulty = Difficulty::PRESETS[form.difficulty];
+
+    for (button, children) in button_query.iter() {
+        let label = match button {
+            AiMenuButton::Difficulty => format!("Level: {}", difficulty),
+            AiMenuButton::Style => format!("Style: {}", form.style),
+            AiMenuButton::Color => format!("Play as: {}", form.color),
+            AiMenuButton::Start | AiMenuButton::TwoPlayers => continue,
+        };
+
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+
+    for entity in details_query.iter() {
+        if let Ok(mut text) = text_query.get_mut(entity) {
+            text.sections[0].value = describe(&difficulty);
+        }
+    }
+}
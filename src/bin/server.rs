@@ -0,0 +1,47 @@
+use std::{net::TcpListener, process, time::Duration};
+
+use bevy_multiplayer_chess::{
+    history::MoveHistory, network::NetworkRole, server::Server, DEFAULT_PORT, RECONNECT_GRACE_SECS,
+};
+
+const USAGE: &str = "usage: server [[<address>:]<port>] [--grace <seconds>]";
+
+fn main() {
+    let mut address = DEFAULT_PORT.to_string();
+    let mut grace_secs = RECONNECT_GRACE_SECS;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--grace" => {
+                grace_secs = args
+                    .next()
+                    .and_then(|secs| secs.parse().ok())
+                    .unwrap_or_else(|| {
+                        eprintln!("--grace expects a number of seconds\n{}", USAGE);
+                        process::exit(2);
+                    });
+            }
+            _ if !arg.starts_with("--") => address = arg,
+            _ => {
+                eprintln!("unknown option '{}'\n{}", arg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    let address = NetworkRole::Host(address).address();
+    let listener = TcpListener::bind(&address).unwrap_or_else(|error| {
+        eprintln!("cannot listen on {}: {}", address, error);
+        process::exit(1);
+    });
+
+    println!("Chess server listening on {}", address);
+
+    let server =
+        Server::new(MoveHistory::default()).with_grace_period(Duration::from_secs(grace_secs));
+    if let Err(error) = server.serve(listener) {
+        eprintln!("server stopped: {}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,494 @@
+use std::sync::OnceLock;
+
+use crate::{
+    piece::{Piece, PieceColor, PieceType},
+    rules::Square,
+};
+
+/// Set of squares, bit `y * 8 + x` standing for `Square::new(x, y)` so that
+/// bits follow the rows of `Board::pieces`, a8 first.
+pub type Bitboard = u64;
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+// Magic numbers for each square, found once with a search over sparse random
+// numbers. Any number hashing every blocker set of the square without two
+// of them with different attacks landing in the same slot would do.
+const ROOK_MAGICS: [u64; 64] = [
+    0x008000908064c000,
+    0x0040200040001000,
+    0x0180100080a0010a,
+    0x8880041000800800,
+    0x1200100201200804,
+    0x0200020004011008,
+    0x2180010000800600,
+    0x0200005088210204,
+    0x0000800080204001,
+    0x1000804000802001,
+    0x8240801000200080,
+    0x8611001004200900,
+    0x008180800c001800,
+    0x0100800200800400,
+    0x0a02000102000408,
+    0x8020802300104280,
+    0x0080004000402000,
+    0xe010104000402000,
+    0x0800808010002000,
+    0xa280210008100100,
+    0x0001818014000800,
+    0xa002010100080400,
+    0x0008040088020130,
+    0x0001020004048845,
+    0x0081826280004004,
+    0x2020810900284000,
+    0x0200100080802000,
+    0x0200080080100080,
+    0x8083080100100500,
+    0x4406000901000400,
+    0x0005020080800100,
+    0x0090204200008114,
+    0x0010400094800420,
+    0x0900804000802002,
+    0x0201001841002000,
+    0x4100080080801000,
+    0x4540040080800800,
+    0x0000800400800200,
+    0x9281800100808200,
+    0x8004048102000854,
+    0x4420802040008006,
+    0x0880500020004002,
+    0x0801200241050010,
+    0x8400080010008080,
+    0x0008000500090010,
+    0x0082009084020008,
+    0x4012000108020004,
+    0x9000104d08860004,
+    0x2004204114800100,
+    0x0148802112400300,
+    0x0202842000100880,
+    0x001b080080900080,
+    0x001a002008100600,
+    0x0004008004020080,
+    0x5181000600040300,
+    0x0000044401128a00,
+    0x8044110480002441,
+    0x1023012082044112,
+    0x00804080200a0012,
+    0x000420310a004a42,
+    0x0023001004020801,
+    0x0882001008040102,
+    0x000230088118020c,
+    0x0000019025040042,
+];
+
+const BISHOP_MAGICS: [u64; 64] = [
+    0x1010220204082a00,
+    0x80e0020202002804,
+    0x2008480104200020,
+    0x000220920280002d,
+    0x32040421000b0284,
+    0x1002080404000400,
+    0x0004160892080040,
+    0x2203024206204201,
+    0x0002404264010200,
+    0x1120908408428124,
+    0xb100424403002280,
+    0x240008060440c288,
+    0x2040040420490400,
+    0x0100620210040022,
+    0x0400084104202028,
+    0x0010050080908820,
+    0x0c90a04490824802,
+    0x000200a008210130,
+    0x0c08001000204010,
+    0x0008000186014480,
+    0x0601044820080021,
+    0x0002000101013100,
+    0x1400a08108080204,
+    0x0250401104485410,
+    0x4820240810142843,
+    0x0009142a20182200,
+    0x0848140048440020,
+    0x2020120000400440,
+    0x0108840200802003,
+    0x0009070082009492,
+    0x020c0c0038424245,
+    0xca44005808210410,
+    0x8011212000500404,
+    0x2028840510101008,
+    0x0004042a00041400,
+    0x0624020080980080,
+    0x1820410040840040,
+    0x2201004202050100,
+    0x402a088a24040224,
+    0x0242061040002400,
+    0x90020202400821a0,
+    0x00c9009004e01002,
+    0x58c2060202023100,
+    0x0000012214040800,
+    0x0210846810100200,
+    0x0004208081010200,
+    0x01a4108404442100,
+    0x8054082c80280106,
+    0x0004144904104208,
+    0x00324c0a11104000,
+    0x1000020231040100,
+    0x2080001042020004,
+    0x0544021020288104,
+    0x1103501408083020,
+    0x4010451004960002,
+    0x003010091c44902c,
+    0x0102402884202000,
+    0x0480804c00841086,
+    0x04602c8602210400,
+    0x0000004000420200,
+    0x0040000020442c18,
+    0x4483804089094100,
+    0x80000b0248020400,
+    0x0045010808008680,
+];
+
+/// Pieces of the board by type and by color, kept alongside
+/// `Board::pieces` for move generation.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Bitboards {
+    by_type: [Bitboard; 6],
+    by_color: [Bitboard; 2],
+}
+
+/// Squares of a bitboard, in increasing bit order.
+pub struct Squares(Bitboard);
+
+/// Magic multiplier hashing the blockers of a slider standing on a square
+/// into its slot of `AttackTables::sliding`.
+#[derive(Debug, Clone, Copy, Default)]
+struct Magic {
+    /// Squares whose occupancy can stop the slider, edges excluded.
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+/// Attacks of each piece from each square, built once on first use.
+struct AttackTables {
+    knight: [Bitboard; 64],
+    king: [Bitboard; 64],
+    pawn: [[Bitboard; 64]; 2],
+    rook: [Magic; 64],
+    bishop: [Magic; 64],
+    sliding: Vec<Bitboard>,
+}
+
+static TABLES: OnceLock<AttackTables> = OnceLock::new();
+
+pub fn square_index(square: Square) -> usize {
+    square.y * 8 + square.x
+}
+
+pub fn index_square(index: usize) -> Square {
+    Square::new(index % 8, index / 8)
+}
+
+pub fn square_bit(square: Square) -> Bitboard {
+    1 << square_index(square)
+}
+
+pub fn squares(bitboard: Bitboard) -> Squares {
+    Squares(bitboard)
+}
+
+pub fn piece_type_index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::King => 0,
+        PieceType::Queen => 1,
+        PieceType::Rook => 2,
+        PieceType::Bishop => 3,
+        PieceType::Knight => 4,
+        PieceType::Pawn => 5,
+    }
+}
+
+pub fn color_index(color: PieceColor) -> usize {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => 1,
+    }
+}
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+
+        let index = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(index_square(index))
+    }
+}
+
+impl Bitboards {
+    pub fn from_pieces(pieces: &[[Option<Piece>; 8]; 8]) -> Self {
+        let mut bitboards = Self::default();
+
+        for (y, row) in pieces.iter().enumerate() {
+            for (x, piece) in row.iter().enumerate() {
+                if let Some(piece) = piece {
+                    bitboards.toggle(*piece, Square::new(x, y));
+                }
+            }
+        }
+
+        bitboards
+    }
+
+    /// Adds `piece` on `square` if it is not there, removes it if it is.
+    pub fn toggle(&mut self, piece: Piece, square: Square) {
+        let bit = square_bit(square);
+        self.by_type[piece_type_index(piece.piece_type)] ^= bit;
+        self.by_color[color_index(piece.color)] ^= bit;
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.by_color[0] | self.by_color[1]
+    }
+
+    pub fn color(&self, color: PieceColor) -> Bitboard {
+        self.by_color[color_index(color)]
+    }
+
+    pub fn pieces(&self, piece_type: PieceType, color: PieceColor) -> Bitboard {
+        self.by_type[piece_type_index(piece_type)] & self.color(color)
+    }
+}
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    tables().knight[square_index(square)]
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    tables().king[square_index(square)]
+}
+
+/// Squares a pawn of `color` on `square` captures on.
+pub fn pawn_attacks(color: PieceColor, square: Square) -> Bitboard {
+    tables().pawn[color_index(color)][square_index(square)]
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.rook[square_index(square)].attacks(&tables.sliding, occupied)
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.bishop[square_index(square)].attacks(&tables.sliding, occupied)
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+fn tables() -> &'static AttackTables {
+    TABLES.get_or_init(AttackTables::new)
+}
+
+impl Magic {
+    fn index(&self, occupied: Bitboard) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+
+    fn attacks(&self, sliding: &[Bitboard], occupied: Bitboard) -> Bitboard {
+        sliding[self.index(occupied)]
+    }
+}
+
+impl AttackTables {
+    fn new() -> Self {
+        let mut tables = Self {
+            knight: [0; 64],
+            king: [0; 64],
+            pawn: [[0; 64]; 2],
+            rook: [Magic::default(); 64],
+            bishop: [Magic::default(); 64],
+            sliding: Vec::new(),
+        };
+
+        for index in 0..64 {
+            let square = index_square(index);
+            let step_targets = |offsets: &[(i32, i32)]| {
+                offsets
+                    .iter()
+                    .filter_map(|&(dx, dy)| square.offset(dx, dy))
+                    .fold(0, |bitboard, target| bitboard | square_bit(target))
+            };
+
+            tables.knight[index] = step_targets(&KNIGHT_OFFSETS);
+            tables.king[index] = step_targets(&KING_OFFSETS);
+            // White pawns move up the rows of `Board::pieces`, black ones down.
+            tables.pawn[0][index] = step_targets(&[(-1, -1), (1, -1)]);
+            tables.pawn[1][index] = step_targets(&[(-1, 1), (1, 1)]);
+        }
+
+        for index in 0..64 {
+            tables.rook[index] = fill_magic(
+                index,
+                &ROOK_DIRECTIONS,
+                ROOK_MAGICS[index],
+                &mut tables.sliding,
+            );
+            tables.bishop[index] = fill_magic(
+                index,
+                &BISHOP_DIRECTIONS,
+                BISHOP_MAGICS[index],
+                &mut tables.sliding,
+            );
+        }
+
+        tables
+    }
+}
+
+/// Squares a slider moving along `directions` from `square` reaches, up to
+/// and including the first occupied square in each direction.
+fn slider_attacks(square: Square, directions: &[(i32, i32)], occupied: Bitboard) -> Bitboard {
+    let mut attacks = 0;
+
+    for &(dx, dy) in directions.iter() {
+        let mut current = square.offset(dx, dy);
+        while let Some(target) = current {
+            attacks |= square_bit(target);
+            if occupied & square_bit(target) != 0 {
+                break;
+            }
+            current = target.offset(dx, dy);
+        }
+    }
+
+    attacks
+}
+
+/// Squares along `directions` whose occupancy matters to a slider on
+/// `square`: the last square of each ray is attacked whether or not it is
+/// occupied, so it is left out.
+fn relevant_mask(square: Square, directions: &[(i32, i32)]) -> Bitboard {
+    let mut mask = 0;
+
+    for &(dx, dy) in directions.iter() {
+        let mut current = square.offset(dx, dy);
+        while let Some(target) = current {
+            current = target.offset(dx, dy);
+            if current.is_some() {
+                mask |= square_bit(target);
+            }
+        }
+    }
+
+    mask
+}
+
+/// Appends to `sliding` the attacks of a slider on `index` for every set of
+/// blockers, each in the slot `magic` hashes it to.
+fn fill_magic(
+    index: usize,
+    directions: &[(i32, i32)],
+    magic: u64,
+    sliding: &mut Vec<Bitboard>,
+) -> Magic {
+    let square = index_square(index);
+    let mask = relevant_mask(square, directions);
+    let bits = mask.count_ones();
+
+    let magic = Magic {
+        mask,
+        magic,
+        shift: 64 - bits,
+        offset: sliding.len(),
+    };
+    sliding.resize(magic.offset + (1 << bits), 0);
+
+    // Every subset of the mask, enumerated with the carry-rippler trick.
+    let mut occupied: Bitboard = 0;
+    loop {
+        let attacks = slider_attacks(square, directions, occupied);
+        let slot = magic.index(occupied);
+        debug_assert!(sliding[slot] == 0 || sliding[slot] == attacks);
+        sliding[slot] = attacks;
+
+        occupied = occupied.wrapping_sub(mask) & mask;
+        if occupied == 0 {
+            break;
+        }
+    }
+
+    magic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the magic lookup against walking the rays for every set of
+    /// blockers, with the squares that cannot block empty then full.
+    fn check_magics(directions: &[(i32, i32)], lookup: fn(Square, Bitboard) -> Bitboard) {
+        for index in 0..64 {
+            let square = index_square(index);
+            let mask = relevant_mask(square, directions);
+
+            let mut blockers: Bitboard = 0;
+            loop {
+                for occupied in [blockers, blockers | !mask] {
+                    assert_eq!(
+                        lookup(square, occupied),
+                        slider_attacks(square, directions, occupied),
+                        "square {} with blockers {:#018x}",
+                        index,
+                        occupied
+                    );
+                }
+
+                blockers = blockers.wrapping_sub(mask) & mask;
+                if blockers == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rook_magics_match_the_rays() {
+        check_magics(&ROOK_DIRECTIONS, rook_attacks);
+    }
+
+    #[test]
+    fn bishop_magics_match_the_rays() {
+        check_magics(&BISHOP_DIRECTIONS, bishop_attacks);
+    }
+}
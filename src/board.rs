@@ -2,12 +2,68 @@ use bevy::{
     prelude::*,
     window::{PrimaryWindow, WindowResized},
 };
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    bitboard::Bitboards,
+    piece::*,
+    rules::{Square, Undo},
+    state::{game_over, GameState},
+    GlobalTextureAtlas, SPRITE_W,
+};
 
-use crate::{piece::*, state::GameState, GlobalTextureAtlas, SPRITE_W};
-
-#[derive(Debug, Resource)]
+// Serialized through the impls below, which fill in the skipped fields once
+// read.
+#[derive(Debug, Clone, Resource, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub struct Board {
     pub pieces: [[Option<Piece>; 8]; 8],
+    pub castling_rights: CastlingRights,
+    /// Square skipped by a pawn that just moved two squares, which an enemy
+    /// pawn may capture onto en passant.
+    pub en_passant: Option<Square>,
+    pub side_to_move: PieceColor,
+    /// Moves since the last capture or pawn move, for the fifty-move rule.
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    /// Hashes of the positions reached since the last capture or pawn move,
+    /// oldest first and not counting the current one, for repetitions.
+    #[serde(skip)]
+    pub position_history: Vec<u64>,
+    /// Zobrist hash of the position, updated along with each move.
+    #[serde(skip)]
+    pub hash: u64,
+    /// `pieces` as bitboards, for move generation. Both are kept in step by
+    /// `make_move`, so `pieces` is not to be written to directly.
+    #[serde(skip)]
+    pub bitboards: Bitboards,
+    /// Moves made on this board since it was set up, for `unmake_move`.
+    #[serde(skip)]
+    pub undo_stack: Vec<Undo>,
+}
+
+/// Sides on which each king may still castle, lost once the king or the
+/// corresponding rook has moved or the rook has been captured.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Board::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut board = Board::deserialize(deserializer)?;
+        board.sync_pieces();
+        Ok(board)
+    }
 }
 
 pub struct BoardPlugin;
@@ -22,13 +78,24 @@ pub struct BoardConfiguration {
 #[derive(Component)]
 struct BoardEntity;
 
+/// Asks for every board entity to be respawned from `Board`, for when the
+/// position was replaced wholesale rather than changed by a single move.
+#[derive(Event)]
+pub struct RedrawBoard;
+
 #[derive(Component)]
 pub struct PieceEntity;
 
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::GameInitEntities), init_board)
-            .add_systems(Update, resize_board.run_if(in_state(GameState::InGame)));
+        app.add_event::<RedrawBoard>()
+            .add_systems(OnEnter(GameState::GameInitEntities), init_board)
+            .add_systems(
+                Update,
+                (resize_board, redraw_board)
+                    .chain()
+                    .run_if(in_state(GameState::InGame).or_else(game_over)),
+            );
     }
 }
 
@@ -42,6 +109,79 @@ impl Default for BoardConfiguration {
     }
 }
 
+impl CastlingRights {
+    pub fn all() -> Self {
+        Self {
+            white_king_side: true,
+            white_queen_side: true,
+            black_king_side: true,
+            black_queen_side: true,
+        }
+    }
+
+    pub fn king_side(&self, color: PieceColor) -> bool {
+        match color {
+            PieceColor::White => self.white_king_side,
+            PieceColor::Black => self.black_king_side,
+        }
+    }
+
+    pub fn queen_side(&self, color: PieceColor) -> bool {
+        match color {
+            PieceColor::White => self.white_queen_side,
+            PieceColor::Black => self.black_queen_side,
+        }
+    }
+
+    pub fn remove(&mut self, color: PieceColor) {
+        match color {
+            PieceColor::White => {
+                self.white_king_side = false;
+                self.white_queen_side = false;
+            }
+            PieceColor::Black => {
+                self.black_king_side = false;
+                self.black_queen_side = false;
+            }
+        }
+    }
+}
+
+impl Board {
+    /// Recomputes the bitboards and hash from `pieces`, once a board has been
+    /// put together field by field.
+    pub fn sync_pieces(&mut self) {
+        self.bitboards = Bitboards::from_pieces(&self.pieces);
+        self.hash = self.zobrist_hash();
+    }
+}
+
+impl BoardConfiguration {
+    /// Board square under `position` in world coordinates, if any.
+    pub fn square_at(&self, position: Vec2) -> Option<Square> {
+        if self.cell_size <= 0.0 {
+            return None;
+        }
+
+        let x = (position.x - self.board_origin.x) / self.cell_size;
+        let y = (self.board_origin.y - position.y) / self.cell_size;
+
+        if (0.0..8.0).contains(&x) && (0.0..8.0).contains(&y) {
+            Some(Square::new(x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+
+    /// World coordinates of the center of `square`.
+    pub fn square_center(&self, square: Square) -> Vec2 {
+        Vec2::new(
+            self.board_origin.x + self.half_cell_size + self.cell_size * square.x as f32,
+            self.board_origin.y - self.half_cell_size - self.cell_size * square.y as f32,
+        )
+    }
+}
+
 impl Default for Board {
     fn default() -> Self {
         let mut pieces: [[Option<Piece>; 8]; 8] = [[None; 8]; 8];
@@ -91,8 +231,8 @@ impl Default for Board {
             });
 
             let pawn_row = if color == PieceColor::Black { 1 } else { 6 };
-            for col in 0..8 {
-                pieces[pawn_row][col] = Some(Piece {
+            for cell in pieces[pawn_row].iter_mut() {
+                *cell = Some(Piece {
                     piece_type: PieceType::Pawn,
                     color,
                     index: get_piece_index(PieceType::Pawn, color),
@@ -104,7 +244,20 @@ impl Default for Board {
         // print_board(&board);
         // board
 
-        Self { pieces }
+        let mut board = Self {
+            pieces,
+            castling_rights: CastlingRights::all(),
+            en_passant: None,
+            side_to_move: PieceColor::White,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            position_history: Vec::new(),
+            hash: 0,
+            bitboards: Bitboards::default(),
+            undo_stack: Vec::new(),
+        };
+        board.sync_pieces();
+        board
     }
 }
 
@@ -124,7 +277,7 @@ pub fn print_board(board: &Board) {
 
         print!("|");
 
-        for (_, cell) in row.iter().enumerate() {
+        for cell in row.iter() {
             let cell_str = match cell {
                 Some(piece) => piece.to_string(),
                 None => " ".to_string(),
@@ -173,7 +326,10 @@ fn init_board(
 
     create_board(&mut commands, &handle, &board, width, height, board_configuration, board_entities);
 
-    next_state.set(GameState::InGame);
+    match board.game_result() {
+        Some(result) => next_state.set(GameState::GameOver(result)),
+        None => next_state.set(GameState::InGame),
+    }
 }
 
 fn create_board(
@@ -269,4 +425,27 @@ fn resize_board(
     let (width, height) = (last_event.unwrap().width, last_event.unwrap().height);
 
     create_board(&mut commands, &handle, &board, width, height, board_configuration, board_entities);
-}
\ No newline at end of file
+}
+
+fn redraw_board(
+    mut commands: Commands,
+    handle: Res<GlobalTextureAtlas>,
+    board: Res<Board>,
+    mut redraw_events: EventReader<RedrawBoard>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    board_configuration: ResMut<BoardConfiguration>,
+    board_entities: Query<Entity, With<BoardEntity>>,
+) {
+    if redraw_events.is_empty() {
+        return;
+    }
+    redraw_events.clear();
+
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    let (width, height) = (window.width(), window.height());
+
+    create_board(&mut commands, &handle, &board, width, height, board_configuration, board_entities);
+}
@@ -0,0 +1,286 @@
+use std::fmt;
+
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+};
+
+use crate::{
+    network::{setup_network_status, NetworkConnection},
+    protocol::{ChatChannel, Message},
+    side_panel::SidePanel,
+    state::{game_over, GameState},
+    FONT_SIZE, MAX_CHAT_LENGTH,
+};
+
+pub struct ChatPlugin;
+
+#[derive(Debug, Clone)]
+pub struct ChatLine {
+    pub channel: ChatChannel,
+    pub from: String,
+    pub text: String,
+}
+
+/// Chat of the game being played or watched, typed into once focused with
+/// `Tab`. Lines from others are dropped while `muted`.
+#[derive(Resource, Debug, Default)]
+pub struct Chat {
+    pub lines: Vec<ChatLine>,
+    pub input: String,
+    pub focused: bool,
+    pub channel: ChatChannel,
+    pub muted: bool,
+}
+
+#[derive(Component)]
+struct ChatLogText;
+
+#[derive(Component)]
+struct ChatInputText;
+
+#[derive(Component, Clone, Copy)]
+enum ChatButton {
+    Channel,
+    Mute,
+}
+
+const MAX_LISTED_LINES: usize = 10;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Chat>()
+            .add_systems(Startup, setup_chat.after(setup_network_status))
+            .add_systems(OnEnter(GameState::MainMenu), reset_chat)
+            .add_systems(
+                Update,
+                (
+                    (focus_chat, type_chat, handle_chat_buttons).chain().run_if(
+                        resource_exists::<NetworkConnection>
+                            .and_then(in_state(GameState::InGame).or_else(game_over)),
+                    ),
+                    update_chat.run_if(resource_changed::<Chat>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+impl Chat {
+    /// Adds a line relayed by the server, unless it comes from someone else
+    /// while the chat is muted.
+    pub fn receive(&mut self, channel: ChatChannel, from: String, text: String, mine: bool) {
+        if self.muted && !mine {
+            return;
+        }
+
+        self.lines.push(ChatLine {
+            channel,
+            from,
+            text,
+        });
+    }
+}
+
+impl fmt::Display for ChatLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.channel {
+            ChatChannel::Game => write!(f, "{}: {}", self.from, self.text),
+            ChatChannel::Spectators => write!(f, "({}) {}: {}", self.channel, self.from, self.text),
+        }
+    }
+}
+
+/// Whether keys typed go to the chat rather than to the board.
+pub fn chat_focused(chat: Res<Chat>) -> bool {
+    chat.focused
+}
+
+fn text_style() -> TextStyle {
+    TextStyle {
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+        ..default()
+    }
+}
+
+fn spawn_button(parent: &mut ChildBuilder, button: ChatButton) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: Color::srgb(0.25, 0.25, 0.3).into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("", text_style()));
+        });
+}
+
+fn setup_chat(
+    mut commands: Commands,
+    connection: Option<Res<NetworkConnection>>,
+    panel_query: Query<Entity, With<SidePanel>>,
+) {
+    let (Some(_), Ok(panel)) = (connection, panel_query.get_single()) else {
+        return;
+    };
+
+    commands.entity(panel).with_children(|parent| {
+        parent
+            .spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    margin: UiRect::top(Val::Auto),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            column_gap: Val::Px(8.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        spawn_button(parent, ChatButton::Channel);
+                        spawn_button(parent, ChatButton::Mute);
+                    });
+                parent.spawn((TextBundle::from_section("", text_style()), ChatLogText));
+                parent.spawn((
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            color: Color::srgb(0.8, 0.8, 0.6),
+                            ..text_style()
+                        },
+                    ),
+                    ChatInputText,
+                ));
+            });
+    });
+}
+
+fn reset_chat(mut chat: ResMut<Chat>) {
+    let muted = chat.muted;
+    *chat = Chat { muted, ..default() };
+}
+
+fn focus_chat(keyboard_input: Res<ButtonInput<KeyCode>>, mut chat: ResMut<Chat>) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        chat.focused = !chat.focused;
+    }
+}
+
+fn type_chat(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut chat: ResMut<Chat>,
+    mut connection: ResMut<NetworkConnection>,
+) {
+    if !chat.focused {
+        keyboard_events.clear();
+        return;
+    }
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        match &event.logical_key {
+            Key::Character(text)
+                if chat.input.chars().count() + text.chars().count() <= MAX_CHAT_LENGTH =>
+            {
+                chat.input.push_str(text);
+            }
+            Key::Space if chat.input.chars().count() < MAX_CHAT_LENGTH => chat.input.push(' '),
+            Key::Backspace => {
+                chat.input.pop();
+            }
+            Key::Enter => {
+                let text = std::mem::take(&mut chat.input);
+                if !text.trim().is_empty() {
+                    let channel = chat.channel;
+                    connection.send(&Message::SendChat { channel, text });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn handle_chat_buttons(
+    button_query: Query<(&Interaction, &ChatButton), Changed<Interaction>>,
+    mut chat: ResMut<Chat>,
+    connection: Res<NetworkConnection>,
+) {
+    for (interaction, &button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            // Players cannot read the spectator channel.
+            ChatButton::Channel if connection.is_watching() => {
+                chat.channel = match chat.channel {
+                    ChatChannel::Game => ChatChannel::Spectators,
+                    ChatChannel::Spectators => ChatChannel::Game,
+                };
+            }
+            ChatButton::Channel => {}
+            ChatButton::Mute => chat.muted = !chat.muted,
+        }
+    }
+}
+
+fn update_chat(
+    chat: Res<Chat>,
+    button_query: Query<(&ChatButton, &Children)>,
+    mut log_query: Query<&mut Text, (With<ChatLogText>, Without<ChatInputText>)>,
+    mut input_query: Query<&mut Text, (With<ChatInputText>, Without<ChatLogText>)>,
+    mut label_query: Query<&mut Text, (Without<ChatLogText>, Without<ChatInputText>)>,
+) {
+    let first = chat.lines.len().saturating_sub(MAX_LISTED_LINES);
+    let log = chat.lines[first..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    for mut text in log_query.iter_mut() {
+        text.sections[0].value = log.clone();
+    }
+
+    let input = if chat.focused {
+        format!("{}> {}_", chat.channel, chat.input)
+    } else {
+        "Press Tab to chat".to_string()
+    };
+    for mut text in input_query.iter_mut() {
+        text.sections[0].value = input.clone();
+    }
+
+    for (button, children) in button_query.iter() {
+        let label = match button {
+            ChatButton::Channel => format!("Channel: {}", chat.channel),
+            ChatButton::Mute => (if chat.muted { "Unmute" } else { "Mute" }).to_string(),
+        };
+
+        for &child in children.iter() {
+            if let Ok(mut text) = label_query.get_mut(child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
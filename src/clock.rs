@@ -0,0 +1,744 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::Board,
+    history::MoveHistory,
+    network::NetworkConnection,
+    options::LaunchOptions,
+    piece::PieceColor,
+    side_panel::{setup_side_panel, SidePanel},
+    state::GameState,
+    FONT_SIZE,
+};
+
+pub struct ClockPlugin;
+
+/// Stretch of the game played on a fixed amount of time, added to the clock
+/// when the stretch begins.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Period {
+    /// Moves to play within the period, `None` for the rest of the game.
+    pub moves: Option<u32>,
+    pub secs: u32,
+}
+
+/// How `TimeControl::bonus_secs` is given on each move.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Bonus {
+    /// Fischer increment, added after every move.
+    #[default]
+    Increment,
+    /// Added back after every move, up to the time the move took.
+    Bronstein,
+    /// Simple delay: the clock only counts down once it has run out.
+    Delay,
+}
+
+/// Periods of the game, played in order, and the bonus given on each move.
+/// When the last period has a move count it is repeated, as in `40/120`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub periods: Vec<Period>,
+    pub bonus_secs: u32,
+    pub bonus: Bonus,
+}
+
+/// Both players' clocks. The clock of the side to move runs, and `press`
+/// hands over to the other side once it has moved.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    time_control: TimeControl,
+    /// Time left to each side, White first, not counting the current move.
+    remaining: [Duration; 2],
+    /// Moves completed by each side, White first.
+    moves: [u32; 2],
+    running: Option<PieceColor>,
+    /// Time spent on the current move.
+    spent: Duration,
+}
+
+/// Clock of the game being played, and how many moves of the game it has
+/// been pressed for.
+#[derive(Resource, Debug)]
+pub struct GameClock {
+    pub clock: Clock,
+    plies: Option<usize>,
+}
+
+#[derive(Component)]
+struct ClockText(PieceColor);
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_clocks.after(setup_side_panel))
+            .add_systems(
+                OnEnter(GameState::GameInitResources),
+                setup_local_clock.run_if(not(resource_exists::<NetworkConnection>)),
+            )
+            .add_systems(
+                OnEnter(GameState::InGame),
+                start_clock.run_if(resource_exists::<GameClock>),
+            )
+            .add_systems(OnEnter(GameState::MainMenu), remove_clock)
+            .add_systems(
+                Update,
+                (
+                    tick_clock.run_if(in_state(GameState::InGame)),
+                    // The server keeps time in network games.
+                    (press_clock, check_flag)
+                        .chain()
+                        .run_if(not(resource_exists::<NetworkConnection>)),
+                )
+                    .chain()
+                    .run_if(resource_exists::<GameClock>),
+            )
+            .add_systems(Update, update_clocks);
+    }
+}
+
+impl TimeControl {
+    /// Single period of `base_secs` with a Fischer increment, or sudden
+    /// death when `increment_secs` is zero.
+    pub fn new(base_secs: u32, increment_secs: u32) -> Self {
+        Self {
+            periods: vec![Period {
+                moves: None,
+                secs: base_secs,
+            }],
+            bonus_secs: increment_secs,
+            bonus: Bonus::Increment,
+        }
+    }
+
+    /// Time controls a seek can be created with, fastest first.
+    pub fn presets() -> Vec<TimeControl> {
+        vec![
+            TimeControl::new(60, 0),
+            TimeControl::new(180, 2),
+            TimeControl::new(300, 3),
+            TimeControl::new(600, 0),
+            TimeControl::new(900, 10),
+            TimeControl::new(1800, 0),
+            TimeControl {
+                bonus: Bonus::Delay,
+                ..TimeControl::new(1800, 5)
+            },
+            "40/90, 30+30".parse().expect("valid time control"),
+        ]
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self.periods.is_empty()
+            && self
+                .periods
+                .iter()
+                .all(|period| period.moves != Some(0) && period.secs > 0)
+            && self.periods[..self.periods.len() - 1]
+                .iter()
+                .all(|period| period.moves.is_some())
+    }
+
+    /// Time added once a side has completed `moves` moves, when that starts
+    /// a new period.
+    fn period_bonus(&self, moves: u32) -> Option<Duration> {
+        let mut end = 0;
+        for (i, period) in self.periods.iter().enumerate() {
+            end += period.moves?;
+            if moves == end {
+                let next = self.periods.get(i + 1).unwrap_or(period);
+                return Some(Duration::from_secs(next.secs as u64));
+            }
+            if moves < end {
+                return None;
+            }
+        }
+
+        let last = self.periods.last()?;
+        let every = last.moves?;
+        ((moves - end) % every == 0).then(|| Duration::from_secs(last.secs as u64))
+    }
+}
+
+impl Clock {
+    pub fn new(time_control: TimeControl) -> Self {
+        let base = Duration::from_secs(time_control.periods.first().map_or(0, |p| p.secs as u64));
+
+        Self {
+            time_control,
+            remaining: [base; 2],
+            moves: [0; 2],
+            running: None,
+            spent: Duration::ZERO,
+        }
+    }
+
+    pub fn time_control(&self) -> &TimeControl {
+        &self.time_control
+    }
+
+    pub fn running(&self) -> Option<PieceColor> {
+        self.running
+    }
+
+    /// Starts the clock of `color`, which is to move.
+    pub fn start(&mut self, color: PieceColor) {
+        self.running = Some(color);
+        self.spent = Duration::ZERO;
+    }
+
+    /// Stops the running clock, charging the current move without bonus.
+    pub fn stop(&mut self) {
+        if let Some(color) = self.running {
+            self.remaining[index(color)] = self.remaining(color);
+        }
+        self.running = None;
+        self.spent = Duration::ZERO;
+    }
+
+    pub fn tick(&mut self, elapsed: Duration) {
+        if self.running.is_some() {
+            self.spent += elapsed;
+        }
+    }
+
+    /// Takes `credit` back off the current move, such as time lost in
+    /// transit, but never more than the move has taken so far.
+    pub fn refund(&mut self, credit: Duration) {
+        self.spent = self.spent.saturating_sub(credit);
+    }
+
+    pub fn remaining(&self, color: PieceColor) -> Duration {
+        let remaining = self.remaining[index(color)];
+        if self.running == Some(color) {
+            remaining.saturating_sub(self.charged())
+        } else {
+            remaining
+        }
+    }
+
+    /// Side whose time ran out, if any.
+    pub fn flag(&self) -> Option<PieceColor> {
+        self.running
+            .filter(|&color| self.charged() >= self.remaining[index(color)])
+    }
+
+    /// Ends the move of the side to move, giving it its bonus and the time
+    /// of the next period if one starts, and starts the other clock.
+    pub fn press(&mut self) {
+        let Some(color) = self.running else {
+            return;
+        };
+
+        let charged = self.charged();
+        let bonus = Duration::from_secs(self.time_control.bonus_secs as u64);
+        let side = index(color);
+
+        self.remaining[side] = self.remaining[side].saturating_sub(charged);
+        self.remaining[side] += match self.time_control.bonus {
+            Bonus::Increment => bonus,
+            Bonus::Bronstein => bonus.min(charged),
+            Bonus::Delay => Duration::ZERO,
+        };
+
+        self.moves[side] += 1;
+        if let Some(time) = self.time_control.period_bonus(self.moves[side]) {
+            self.remaining[side] += time;
+        }
+
+        self.start(color.opponent());
+    }
+
+    /// Forgets the last move of `color`, which was taken back, along with
+    /// the time of the period it started if it did. The time spent on it
+    /// and its bonus stay as they are.
+    pub fn take_back(&mut self, color: PieceColor) {
+        let side = index(color);
+        if self.moves[side] == 0 {
+            return;
+        }
+
+        if let Some(time) = self.time_control.period_bonus(self.moves[side]) {
+            self.remaining[side] = self.remaining[side].saturating_sub(time);
+        }
+        self.moves[side] -= 1;
+    }
+
+    /// Sets the clocks to the time kept by the server, the clock of `running`
+    /// starting over from there.
+    pub fn sync(&mut self, white: Duration, black: Duration, running: Option<PieceColor>) {
+        self.remaining = [white, black];
+        self.running = running;
+        self.spent = Duration::ZERO;
+    }
+
+    /// Part of the current move taken off the clock.
+    fn charged(&self) -> Duration {
+        match self.time_control.bonus {
+            Bonus::Delay => self
+                .spent
+                .saturating_sub(Duration::from_secs(self.time_control.bonus_secs as u64)),
+            Bonus::Increment | Bonus::Bronstein => self.spent,
+        }
+    }
+}
+
+impl GameClock {
+    pub fn new(time_control: TimeControl) -> Self {
+        Self {
+            clock: Clock::new(time_control),
+            plies: None,
+        }
+    }
+}
+
+fn index(color: PieceColor) -> usize {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => 1,
+    }
+}
+
+fn format_minutes(secs: u32) -> String {
+    if secs % 60 == 0 {
+        (secs / 60).to_string()
+    } else {
+        (secs as f32 / 60.0).to_string()
+    }
+}
+
+/// Formats the time control the usual way, such as `5+3`, `90 d5` or
+/// `40/90, 30+30`, minutes first and the bonus in seconds.
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, period) in self.periods.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            if let Some(moves) = period.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", format_minutes(period.secs))?;
+        }
+
+        match self.bonus {
+            Bonus::Increment => write!(f, "+{}", self.bonus_secs),
+            Bonus::Bronstein => write!(f, " b{}", self.bonus_secs),
+            Bonus::Delay => write!(f, " d{}", self.bonus_secs),
+        }
+    }
+}
+
+/// Parses the notation `Display` writes. The bonus may be left out for
+/// sudden death.
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (periods, bonus, bonus_secs) = match text.rfind(['+', 'b', 'd']) {
+            Some(at) => {
+                let bonus = match &text[at..at + 1] {
+                    "+" => Bonus::Increment,
+                    "b" => Bonus::Bronstein,
+                    _ => Bonus::Delay,
+                };
+                let secs = text[at + 1..]
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid bonus in '{}'", text))?;
+                (&text[..at], bonus, secs)
+            }
+            None => (text, Bonus::Increment, 0),
+        };
+
+        let periods = periods
+            .split(',')
+            .map(|period| {
+                let (moves, minutes) = match period.split_once('/') {
+                    Some((moves, minutes)) => (Some(moves.trim()), minutes),
+                    None => (None, period),
+                };
+                let moves = moves
+                    .map(|moves| moves.parse::<u32>())
+                    .transpose()
+                    .map_err(|_| format!("invalid move count in '{}'", period.trim()))?;
+                let minutes: f32 = minutes
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid minutes in '{}'", period.trim()))?;
+
+                Ok(Period {
+                    moves,
+                    secs: (minutes * 60.0).round() as u32,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let time_control = TimeControl {
+            periods,
+            bonus_secs,
+            bonus,
+        };
+        if !time_control.is_valid() {
+            return Err(format!("invalid time control '{}'", text));
+        }
+
+        Ok(time_control)
+    }
+}
+
+/// Formats time left as `m:ss`, or `h:mm:ss`, with tenths under ten seconds.
+fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else if secs >= 10 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("0:{:02}.{}", secs, time.subsec_millis() / 100)
+    }
+}
+
+fn setup_clocks(mut commands: Commands, panel_query: Query<Entity, With<SidePanel>>) {
+    let Ok(panel) = panel_query.get_single() else {
+        return;
+    };
+
+    let clocks = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for color in [PieceColor::Black, PieceColor::White] {
+                parent.spawn((
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: FONT_SIZE * 1.5,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    ClockText(color),
+                ));
+            }
+        })
+        .id();
+
+    commands.entity(panel).insert_children(0, &[clocks]);
+}
+
+fn setup_local_clock(mut commands: Commands, options: Res<LaunchOptions>) {
+    if let Some(time_control) = options.clock.clone() {
+        commands.insert_resource(GameClock::new(time_control));
+    }
+}
+
+fn start_clock(mut game_clock: ResMut<GameClock>, board: Res<Board>, history: Res<MoveHistory>) {
+    if game_clock.plies.is_none() {
+        game_clock.plies = Some(history.moves.len());
+        game_clock.clock.start(board.side_to_move);
+    }
+}
+
+fn remove_clock(mut commands: Commands) {
+    commands.remove_resource::<GameClock>();
+}
+
+fn tick_clock(time: Res<Time>, mut game_clock: ResMut<GameClock>) {
+    game_clock.clock.tick(time.delta());
+}
+
+/// Presses the clock for the moves played since it was last pressed, or
+/// hands it back to the side to move after a takeback.
+fn press_clock(mut game_clock: ResMut<GameClock>, history: Res<MoveHistory>, board: Res<Board>) {
+    let Some(plies) = game_clock.plies else {
+        return;
+    };
+    if history.moves.len() == plies {
+        return;
+    }
+
+    if history.moves.len() < plies {
+        let mut color = board.side_to_move;
+        for _ in history.moves.len()..plies {
+            game_clock.clock.take_back(color);
+            color = color.opponent();
+        }
+        game_clock.clock.stop();
+        game_clock.clock.start(board.side_to_move);
+    }
+    for _ in plies..history.moves.len() {
+        game_clock.clock.press();
+    }
+    game_clock.plies = Some(history.moves.len());
+}
+
+fn check_flag(
+    mut game_clock: ResMut<GameClock>,
+    board: Res<Board>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if let GameState::GameOver(_) = state.get() {
+        game_clock.clock.stop();
+        return;
+    }
+
+    if let Some(color) = game_clock.clock.flag() {
+        game_clock.clock.stop();
+        next_state.set(GameState::GameOver(board.timeout_result(color)));
+    }
+}
+
+fn update_clocks(
+    game_clock: Option<Res<GameClock>>,
+    mut text_query: Query<(&mut Text, &ClockText)>,
+) {
+    for (mut text, &ClockText(color)) in text_query.iter_mut() {
+        let Some(game_clock) = game_clock.as_ref() else {
+            if !text.sections[0].value.is_empty() {
+                text.sections[0].value.clear();
+            }
+            continue;
+        };
+
+        let remaining = game_clock.clock.remaining(color);
+        text.sections[0].value = format!("{}  {}", color, format_time(remaining));
+        text.sections[0].style.color = if game_clock.clock.running() != Some(color) {
+            Color::srgb(0.6, 0.6, 0.6)
+        } else if remaining < Duration::from_secs(10) {
+            Color::srgb(1.0, 0.4, 0.4)
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    fn started(time_control: &str) -> Clock {
+        let mut clock = Clock::new(time_control.parse().unwrap());
+        clock.start(PieceColor::White);
+        clock
+    }
+
+    #[test]
+    fn parses_and_formats_time_controls() {
+        let blitz: TimeControl = "5+3".parse().unwrap();
+        assert_eq!(blitz, TimeControl::new(300, 3));
+
+        let classical: TimeControl = "40/90, 30+30".parse().unwrap();
+        assert_eq!(
+            classical.periods,
+            [
+                Period {
+                    moves: Some(40),
+                    secs: 5400
+                },
+                Period {
+                    moves: None,
+                    secs: 1800
+                },
+            ]
+        );
+        assert_eq!(classical.bonus_secs, 30);
+
+        assert_eq!("90 d5".parse::<TimeControl>().unwrap().bonus, Bonus::Delay);
+        assert_eq!(
+            "15 b10".parse::<TimeControl>().unwrap().bonus,
+            Bonus::Bronstein
+        );
+        assert_eq!(
+            "10".parse::<TimeControl>().unwrap(),
+            TimeControl::new(600, 0)
+        );
+
+        for text in [
+            "5+3",
+            "0.5+0",
+            "90 d5",
+            "15 b10",
+            "40/90, 30+30",
+            "40/120+0",
+        ] {
+            assert_eq!(text.parse::<TimeControl>().unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_time_controls() {
+        for text in [
+            "",
+            "abc",
+            "5+x",
+            "x/5",
+            "0+2",
+            "0/90",
+            "40/0, 30+30",
+            "40/90, 0+30",
+            "90, 30+30",
+        ] {
+            assert!(text.parse::<TimeControl>().is_err(), "{} parsed", text);
+        }
+        assert!(TimeControl::presets().iter().all(TimeControl::is_valid));
+    }
+
+    #[test]
+    fn periods_start_after_their_move_count() {
+        let time_control: TimeControl = "40/90, 20/60, 30+30".parse().unwrap();
+        assert_eq!(time_control.period_bonus(39), None);
+        assert_eq!(time_control.period_bonus(40), Some(secs(3600)));
+        assert_eq!(time_control.period_bonus(59), None);
+        assert_eq!(time_control.period_bonus(60), Some(secs(1800)));
+        assert_eq!(time_control.period_bonus(100), None);
+
+        let repeating: TimeControl = "40/120".parse().unwrap();
+        assert_eq!(repeating.period_bonus(40), Some(secs(7200)));
+        assert_eq!(repeating.period_bonus(41), None);
+        assert_eq!(repeating.period_bonus(80), Some(secs(7200)));
+        assert_eq!(repeating.period_bonus(120), Some(secs(7200)));
+    }
+
+    #[test]
+    fn press_charges_the_move_and_adds_the_increment() {
+        let mut clock = started("5+3");
+        clock.tick(secs(10));
+        assert_eq!(clock.remaining(PieceColor::White), secs(290));
+
+        clock.press();
+        assert_eq!(clock.remaining(PieceColor::White), secs(293));
+        assert_eq!(clock.running(), Some(PieceColor::Black));
+
+        clock.tick(secs(1));
+        clock.stop();
+        assert_eq!(clock.remaining(PieceColor::Black), secs(299));
+        assert_eq!(clock.running(), None);
+    }
+
+    #[test]
+    fn refund_never_exceeds_the_move() {
+        let mut clock = started("5+0");
+        clock.tick(secs(2));
+        clock.refund(Duration::from_millis(500));
+        assert_eq!(
+            clock.remaining(PieceColor::White),
+            Duration::from_millis(298_500)
+        );
+
+        clock.refund(secs(10));
+        assert_eq!(clock.remaining(PieceColor::White), secs(300));
+    }
+
+    #[test]
+    fn bronstein_gives_back_at_most_the_time_used() {
+        let mut clock = started("5 b3");
+        clock.tick(secs(2));
+        clock.press();
+        assert_eq!(clock.remaining(PieceColor::White), secs(300));
+
+        clock.press();
+        clock.tick(secs(5));
+        clock.press();
+        assert_eq!(clock.remaining(PieceColor::White), secs(298));
+    }
+
+    #[test]
+    fn delay_runs_before_the_clock_does() {
+        let mut clock = started("5 d3");
+        clock.tick(secs(2));
+        assert_eq!(clock.remaining(PieceColor::White), secs(300));
+
+        clock.tick(secs(2));
+        assert_eq!(clock.remaining(PieceColor::White), secs(299));
+
+        clock.press();
+        assert_eq!(clock.remaining(PieceColor::White), secs(299));
+    }
+
+    #[test]
+    fn flag_falls_once_time_runs_out() {
+        let mut clock = started("1+0");
+        clock.tick(secs(59));
+        assert_eq!(clock.flag(), None);
+
+        clock.tick(secs(1));
+        assert_eq!(clock.flag(), Some(PieceColor::White));
+        assert_eq!(clock.remaining(PieceColor::White), Duration::ZERO);
+    }
+
+    #[test]
+    fn next_period_is_added_after_the_last_move_of_the_first() {
+        let mut clock = started("40/90, 30+30");
+        for _ in 0..39 {
+            clock.tick(secs(10));
+            clock.press();
+            clock.tick(secs(10));
+            clock.press();
+        }
+        assert_eq!(
+            clock.remaining(PieceColor::White),
+            secs(5400 - 390 + 39 * 30)
+        );
+
+        clock.tick(secs(10));
+        clock.press();
+        assert_eq!(
+            clock.remaining(PieceColor::White),
+            secs(5400 - 400 + 40 * 30 + 1800)
+        );
+        assert_eq!(
+            clock.remaining(PieceColor::Black),
+            secs(5400 - 390 + 39 * 30)
+        );
+
+        clock.tick(secs(10));
+        clock.press();
+        clock.tick(secs(10));
+        clock.press();
+        assert_eq!(
+            clock.remaining(PieceColor::White),
+            secs(5400 - 410 + 41 * 30 + 1800)
+        );
+    }
+
+    #[test]
+    fn takebacks_across_a_period_do_not_add_it_twice() {
+        let mut clock = started("40/90, 30+30");
+        for _ in 0..40 {
+            clock.tick(secs(10));
+            clock.press();
+            clock.tick(secs(10));
+            clock.press();
+        }
+        let white = secs(5400 - 400 + 40 * 30 + 1800);
+        assert_eq!(clock.remaining(PieceColor::White), white);
+
+        clock.take_back(PieceColor::Black);
+        clock.take_back(PieceColor::White);
+        clock.stop();
+        clock.start(PieceColor::White);
+        assert_eq!(clock.remaining(PieceColor::White), white - secs(1800));
+
+        clock.press();
+        assert_eq!(clock.remaining(PieceColor::White), white + secs(30));
+        clock.press();
+        assert_eq!(
+            clock.remaining(PieceColor::Black),
+            secs(5400 - 400 + 41 * 30 + 1800)
+        );
+    }
+}
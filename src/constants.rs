@@ -8,5 +8,28 @@ pub const SPRITE_SHEET_H: u32 = 3;
 // BOARD
 // pub const BOARD_SCALE: f32 = 0.88;
 
+// UI
+pub const SIDE_PANEL_WIDTH: f32 = 300.0;
+pub const FONT_SIZE: f32 = 20.0;
+
 // Colors
 pub const BG_COLOR: (u8, u8, u8) = (48, 46, 43);
+
+// PGN
+pub const PGN_EXPORT_PATH: &str = "game.pgn";
+
+// NETWORK
+pub const DEFAULT_PORT: u16 = 7878;
+pub const RECONNECT_GRACE_SECS: u64 = 60;
+pub const WRITE_TIMEOUT_SECS: u64 = 10;
+pub const RECONNECT_INTERVAL_SECS: f32 = 2.0;
+pub const CONNECT_TIMEOUT_SECS: u64 = 5;
+pub const PING_INTERVAL_SECS: u64 = 2;
+pub const CLOCK_TICK_MILLIS: u64 = 50;
+pub const MAX_LAG_COMPENSATION_MILLIS: u64 = 1000;
+
+// CHAT
+pub const MAX_CHAT_LENGTH: usize = 200;
+
+// ENGINE
+pub const ENGINE_HASH_ENTRIES: usize = 1 << 18;
@@ -1,5 +1,7 @@
 use bevy::{prelude::*, render::{settings::{Backends, RenderCreation, WgpuSettings}, RenderPlugin}};
 
+use crate::SIDE_PANEL_WIDTH;
+
 pub struct MyDefaultPlugins;
 
 impl Plugin for MyDefaultPlugins {
@@ -16,7 +18,7 @@ impl Plugin for MyDefaultPlugins {
                 // .set(ImagePlugin::default_nearest())
                 .set(WindowPlugin {
                     primary_window: Some(Window {
-                        resolution: (800.0, 800.0).into(),
+                        resolution: (800.0 + SIDE_PANEL_WIDTH, 800.0).into(),
                         resizable: true,
                         focused: true,
                         title: "Chess".to_string(),
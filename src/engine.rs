@@ -0,0 +1,686 @@
+use std::{
+    cmp::Reverse,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    bitboard::{square_index, squares},
+    board::Board,
+    piece::{PieceColor, PieceType},
+    rules::{Move, Square},
+    ENGINE_HASH_ENTRIES,
+};
+
+/// Score of a mate on the board, mates further away scoring one less per
+/// ply so that the quickest one is preferred.
+pub const MATE_SCORE: i32 = 30_000;
+const INFINITY: i32 = 32_000;
+const MAX_PLY: usize = 64;
+/// Nodes searched between two looks at the clock and the stop flag.
+const CHECK_INTERVAL: u64 = 2048;
+
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+// Piece-square tables from White's side, a8 first like `Board::pieces`,
+// after Tomasz Michniewski's simplified evaluation function.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+/// Game phase of a full set of pieces, knights and bishops counting 1,
+/// rooks 2 and queens 4. The king moves from its middlegame table to its
+/// endgame one as the phase drops.
+const OPENING_PHASE: i32 = 24;
+
+/// When to stop searching: after `depth` plies, once `time` has passed, or
+/// whichever comes first. With neither, the search only ends when stopped.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub time: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// Score of `best_move` in centipawns for the side to move.
+    pub score: i32,
+    /// Depth of the last iteration completed.
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+/// Alpha-beta searcher, keeping its transposition table from one search to
+/// the next.
+pub struct Engine {
+    table: TranspositionTable,
+    /// How much the engine thinks a draw is worse for it than an even
+    /// position, making it play on rather than repeat moves when above zero
+    /// and steer for draws when below.
+    pub contempt: i32,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Bound {
+    Exact,
+    /// The score is at least this much: the search failed high.
+    Lower,
+    /// The score is at most this much: no move raised alpha.
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TableEntry {
+    hash: u64,
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+/// Results of earlier searches by position hash, one entry per slot, the
+/// newest replacing the older.
+struct TranspositionTable {
+    entries: Vec<Option<TableEntry>>,
+}
+
+/// State of a single search.
+struct Search<'a> {
+    table: &'a mut TranspositionTable,
+    stop: &'a AtomicBool,
+    /// Side the search is for, the one `contempt` is counted against.
+    engine_color: PieceColor,
+    contempt: i32,
+    deadline: Option<Instant>,
+    stopped: bool,
+    nodes: u64,
+    /// Quiet moves which caused a cutoff at each ply, tried early at that
+    /// ply elsewhere in the tree.
+    killers: [[Option<Move>; 2]; MAX_PLY],
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self {
+            table: TranspositionTable {
+                entries: vec![None; ENGINE_HASH_ENTRIES],
+            },
+            contempt: 0,
+        }
+    }
+
+    /// Searches `board` with iterative deepening until `limits` are reached
+    /// or `stop` is set, returning the best move of the deepest iteration
+    /// completed.
+    pub fn search(
+        &mut self,
+        board: &Board,
+        limits: SearchLimits,
+        stop: &AtomicBool,
+    ) -> SearchResult {
+        let mut board = board.clone();
+        let mut search = Search {
+            table: &mut self.table,
+            stop,
+            engine_color: board.side_to_move,
+            contempt: self.contempt,
+            deadline: limits.time.map(|time| Instant::now() + time),
+            stopped: false,
+            nodes: 0,
+            killers: [[None; 2]; MAX_PLY],
+        };
+
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+        };
+
+        let max_depth = limits.depth.unwrap_or(MAX_PLY as u32 - 1).max(1);
+        for depth in 1..=max_depth {
+            let (best_move, score) = search.root(&mut board, depth, result.best_move);
+
+            // A move from an unfinished iteration is still better than none.
+            if search.stopped {
+                if result.best_move.is_none() {
+                    result.best_move = best_move;
+                }
+                break;
+            }
+
+            result = SearchResult {
+                best_move,
+                score,
+                depth,
+                nodes: search.nodes,
+            };
+
+            // Nothing deeper changes a forced mate or a position with no moves.
+            if best_move.is_none() || score.abs() >= MATE_SCORE - MAX_PLY as i32 {
+                break;
+            }
+        }
+
+        result.nodes = search.nodes;
+        result
+    }
+}
+
+impl TranspositionTable {
+    fn slot(&self, hash: u64) -> usize {
+        (hash % self.entries.len() as u64) as usize
+    }
+
+    fn probe(&self, hash: u64) -> Option<TableEntry> {
+        self.entries[self.slot(hash)].filter(|entry| entry.hash == hash)
+    }
+
+    fn store(&mut self, entry: TableEntry) {
+        let slot = self.slot(entry.hash);
+        self.entries[slot] = Some(entry);
+    }
+}
+
+impl Search<'_> {
+    /// Searches every move of the root position, `previous_best` first.
+    fn root(
+        &mut self,
+        board: &mut Board,
+        depth: u32,
+        previous_best: Option<Move>,
+    ) -> (Option<Move>, i32) {
+        let mut moves = board.legal_moves(board.side_to_move);
+        self.order_moves(board, &mut moves, previous_best, 0);
+
+        let mut alpha = -INFINITY;
+        let mut best_move = None;
+
+        for mv in moves {
+            board.make_move(mv);
+            let score = -self.negamax(board, depth - 1, 1, -INFINITY, -alpha);
+            board.unmake_move();
+
+            if self.stopped {
+                break;
+            }
+            if score > alpha || best_move.is_none() {
+                alpha = score;
+                best_move = Some(mv);
+            }
+        }
+
+        if best_move.is_none() && !self.stopped {
+            alpha = if board.is_in_check(board.side_to_move) {
+                -MATE_SCORE
+            } else {
+                self.draw_score(board)
+            };
+        }
+
+        (best_move, alpha)
+    }
+
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+
+        if board.halfmove_clock >= 100
+            || board.repetitions() > 1
+            || board.is_insufficient_material()
+        {
+            return self.draw_score(board);
+        }
+        if ply >= MAX_PLY {
+            return evaluate(board);
+        }
+
+        let in_check = board.is_in_check(board.side_to_move);
+        // Look one ply further out of checks, so as not to stop in the middle
+        // of a forcing sequence.
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        let entry = self.table.probe(board.hash);
+        if let Some(entry) = entry.filter(|entry| entry.depth >= depth) {
+            let score = score_from_table(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => {}
+            }
+        }
+
+        let mut moves = board.legal_moves(board.side_to_move);
+        if moves.is_empty() {
+            return if in_check {
+                -MATE_SCORE + ply as i32
+            } else {
+                self.draw_score(board)
+            };
+        }
+        self.order_moves(
+            board,
+            &mut moves,
+            entry.and_then(|entry| entry.best_move),
+            ply,
+        );
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+
+        for mv in moves {
+            board.make_move(mv);
+            let score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha);
+            board.unmake_move();
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                if !mv.is_capture() && mv.promotion.is_none() {
+                    self.store_killer(mv, ply);
+                }
+                break;
+            }
+        }
+
+        let bound = if best_score <= original_alpha {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.store(TableEntry {
+            hash: board.hash,
+            depth,
+            score: score_to_table(best_score, ply),
+            bound,
+            best_move,
+        });
+
+        best_score
+    }
+
+    /// Plays out captures and promotions until the position is quiet, so
+    /// that the evaluation is not taken in the middle of an exchange.
+    fn quiescence(&mut self, board: &mut Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+
+        let stand_pat = evaluate(board);
+        if stand_pat >= beta || ply >= MAX_PLY {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves: Vec<Move> = board
+            .legal_moves(board.side_to_move)
+            .into_iter()
+            .filter(|mv| mv.is_capture() || mv.promotion.is_some())
+            .collect();
+        self.order_moves(board, &mut moves, None, ply);
+
+        for mv in moves {
+            board.make_move(mv);
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move();
+
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+
+    /// Sorts `moves` so that the likeliest to be best come first: the move
+    /// from the transposition table, then captures of the most valuable
+    /// piece by the least valuable one, promotions, and killer moves.
+    fn order_moves(&self, board: &Board, moves: &mut [Move], best_move: Option<Move>, ply: usize) {
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+
+        moves.sort_by_cached_key(|&mv| {
+            let priority = if Some(mv) == best_move {
+                1_000_000
+            } else if mv.is_capture() {
+                let victim = board
+                    .piece_at(mv.to)
+                    .map_or(PieceType::Pawn, |piece| piece.piece_type);
+                let attacker = board
+                    .piece_at(mv.from)
+                    .map_or(PieceType::Pawn, |piece| piece.piece_type);
+                100_000 + 10 * piece_value(victim) - piece_value(attacker)
+            } else if let Some(piece_type) = mv.promotion {
+                90_000 + piece_value(piece_type)
+            } else if killers[0] == Some(mv) {
+                80_000
+            } else if killers[1] == Some(mv) {
+                79_000
+            } else {
+                0
+            };
+            Reverse(priority)
+        });
+    }
+
+    fn store_killer(&mut self, mv: Move, ply: usize) {
+        let Some(killers) = self.killers.get_mut(ply) else {
+            return;
+        };
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+    }
+
+    /// Score of a drawn position for the side to move in it.
+    fn draw_score(&self, board: &Board) -> i32 {
+        if board.side_to_move == self.engine_color {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped && self.nodes % CHECK_INTERVAL == 0 {
+            self.stopped = self.stop.load(Ordering::Relaxed)
+                || self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline);
+        }
+        self.stopped
+    }
+}
+
+/// Mate scores are stored relative to the position they were found in
+/// rather than to the root, as the same position can come up at any ply.
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE_SCORE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -MATE_SCORE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE_SCORE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -MATE_SCORE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// Value of a piece in centipawns.
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 20_000,
+    }
+}
+
+/// Static evaluation of `board` in centipawns for the side to move, from
+/// material and where the pieces stand.
+pub fn evaluate(board: &Board) -> i32 {
+    let mut phase = 0;
+    for color in [PieceColor::White, PieceColor::Black] {
+        for (piece_type, weight) in [
+            (PieceType::Knight, 1),
+            (PieceType::Bishop, 1),
+            (PieceType::Rook, 2),
+            (PieceType::Queen, 4),
+        ] {
+            phase += weight * board.bitboards.pieces(piece_type, color).count_ones() as i32;
+        }
+    }
+    let phase = phase.min(OPENING_PHASE);
+
+    let mut score = 0;
+    for color in [PieceColor::White, PieceColor::Black] {
+        let sign = if color == PieceColor::White { 1 } else { -1 };
+
+        for piece_type in PIECE_TYPES {
+            for square in squares(board.bitboards.pieces(piece_type, color)) {
+                let index = table_index(square, color);
+                let placement = match piece_type {
+                    PieceType::Pawn => PAWN_TABLE[index],
+                    PieceType::Knight => KNIGHT_TABLE[index],
+                    PieceType::Bishop => BISHOP_TABLE[index],
+                    PieceType::Rook => ROOK_TABLE[index],
+                    PieceType::Queen => QUEEN_TABLE[index],
+                    PieceType::King => {
+                        (KING_MIDDLEGAME_TABLE[index] * phase
+                            + KING_ENDGAME_TABLE[index] * (OPENING_PHASE - phase))
+                            / OPENING_PHASE
+                    }
+                };
+                score += sign * (piece_value(piece_type) + placement);
+            }
+        }
+    }
+
+    match board.side_to_move {
+        PieceColor::White => score,
+        PieceColor::Black => -score,
+    }
+}
+
+/// Index into the piece-square tables for a piece of `color` on `square`,
+/// Black's pieces reading the tables upside down.
+fn table_index(square: Square, color: PieceColor) -> usize {
+    match color {
+        PieceColor::White => square_index(square),
+        PieceColor::Black => square_index(square) ^ 56,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str, depth: u32) -> SearchResult {
+        let board = Board::from_fen(fen).unwrap();
+        let limits = SearchLimits {
+            depth: Some(depth),
+            time: None,
+        };
+        Engine::new().search(&board, limits, &AtomicBool::new(false))
+    }
+
+    fn best_move(result: &SearchResult) -> String {
+        result
+            .best_move
+            .map(|mv| mv.to_string())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let back_rank = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2);
+        assert_eq!(best_move(&back_rank), "a1a8");
+        assert_eq!(back_rank.score, MATE_SCORE - 1);
+
+        let scholars_mate = search(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            2,
+        );
+        assert_eq!(best_move(&scholars_mate), "h5f7");
+        assert_eq!(scholars_mate.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        let sacrifice = search(
+            "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 10",
+            4,
+        );
+        assert_eq!(best_move(&sacrifice), "d5f6");
+        assert_eq!(sacrifice.score, MATE_SCORE - 3);
+
+        let rook_ending = search("k7/8/2K5/8/8/8/8/7R w - - 0 1", 4);
+        assert_eq!(rook_ending.score, MATE_SCORE - 3);
+    }
+
+    #[test]
+    fn saves_attacked_pieces_at_depth_three() {
+        let knight = search(
+            "rnbqkbnr/pppp1ppp/8/4p3/3N4/8/PPPPPPPP/RNBQKB1R w KQkq - 0 1",
+            3,
+        );
+        assert!(
+            best_move(&knight).starts_with("d4"),
+            "{}",
+            best_move(&knight)
+        );
+        assert!(knight.score > -piece_value(PieceType::Pawn));
+
+        let bishop = search(
+            "rnbqkbnr/ppp2ppp/8/3pp3/4B3/8/PPPPPPPP/RNBQK1NR w KQkq - 0 1",
+            3,
+        );
+        assert!(
+            best_move(&bishop).starts_with("e4"),
+            "{}",
+            best_move(&bishop)
+        );
+        assert!(bishop.score > -piece_value(PieceType::Pawn));
+    }
+
+    #[test]
+    fn has_no_move_to_offer_when_there_is_none() {
+        let stalemate = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 3);
+        assert_eq!(stalemate.best_move, None);
+        assert_eq!(stalemate.score, 0);
+    }
+}
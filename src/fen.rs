@@ -0,0 +1,378 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    bitboard::Bitboards,
+    board::{Board, CastlingRights},
+    piece::{get_piece_index, Piece, PieceColor, PieceType},
+    rules::Square,
+};
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    WrongRankLength { rank: usize, length: usize },
+    InvalidPiece(char),
+    MissingKing(PieceColor),
+    InvalidSideToMove(String),
+    InvalidCastlingRights(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount(count) => {
+                write!(f, "expected 4 to 6 space-separated fields, found {}", count)
+            }
+            FenError::WrongRankCount(count) => {
+                write!(f, "piece placement must describe 8 ranks, found {}", count)
+            }
+            FenError::WrongRankLength { rank, length } => {
+                write!(f, "rank {} describes {} squares instead of 8", rank, length)
+            }
+            FenError::InvalidPiece(c) => write!(f, "'{}' is not a valid piece", c),
+            FenError::MissingKing(color) => write!(f, "{} must have exactly one king", color),
+            FenError::InvalidSideToMove(text) => {
+                write!(f, "side to move must be 'w' or 'b', found '{}'", text)
+            }
+            FenError::InvalidCastlingRights(text) => {
+                write!(f, "'{}' is not a valid castling availability", text)
+            }
+            FenError::InvalidEnPassant(text) => {
+                write!(f, "'{}' is not a valid en passant target square", text)
+            }
+            FenError::InvalidHalfmoveClock(text) => {
+                write!(f, "'{}' is not a valid halfmove clock", text)
+            }
+            FenError::InvalidFullmoveNumber(text) => {
+                write!(f, "'{}' is not a valid fullmove number", text)
+            }
+        }
+    }
+}
+
+impl Error for FenError {}
+
+fn piece_from_char(c: char) -> Option<Piece> {
+    let piece_type = match c.to_ascii_lowercase() {
+        'k' => PieceType::King,
+        'q' => PieceType::Queen,
+        'r' => PieceType::Rook,
+        'b' => PieceType::Bishop,
+        'n' => PieceType::Knight,
+        'p' => PieceType::Pawn,
+        _ => return None,
+    };
+    let color = if c.is_ascii_uppercase() {
+        PieceColor::White
+    } else {
+        PieceColor::Black
+    };
+
+    Some(Piece {
+        piece_type,
+        color,
+        index: get_piece_index(piece_type, color),
+    })
+}
+
+impl Board {
+    /// Parses a position in Forsyth-Edwards Notation. The halfmove clock and
+    /// fullmove number may be omitted, as some tools do.
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if !(4..=6).contains(&fields.len()) {
+            return Err(FenError::WrongFieldCount(fields.len()));
+        }
+
+        let mut pieces: [[Option<Piece>; 8]; 8] = [[None; 8]; 8];
+
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
+
+        for (y, rank) in ranks.iter().enumerate() {
+            let mut x = 0;
+            for c in rank.chars() {
+                if let Some(empty) = c.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                    x += empty as usize;
+                    continue;
+                }
+
+                let piece = piece_from_char(c).ok_or(FenError::InvalidPiece(c))?;
+                if x < 8 {
+                    pieces[y][x] = Some(piece);
+                }
+                x += 1;
+            }
+
+            if x != 8 {
+                return Err(FenError::WrongRankLength {
+                    rank: 8 - y,
+                    length: x,
+                });
+            }
+        }
+
+        for color in [PieceColor::White, PieceColor::Black] {
+            let kings = pieces
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|piece| piece.piece_type == PieceType::King && piece.color == color)
+                .count();
+            if kings != 1 {
+                return Err(FenError::MissingKing(color));
+            }
+        }
+
+        let side_to_move = match fields[1] {
+            "w" => PieceColor::White,
+            "b" => PieceColor::Black,
+            other => return Err(FenError::InvalidSideToMove(other.to_string())),
+        };
+
+        let mut castling_rights = CastlingRights::default();
+        if fields[2] != "-" {
+            for c in fields[2].chars() {
+                let right = match c {
+                    'K' => &mut castling_rights.white_king_side,
+                    'Q' => &mut castling_rights.white_queen_side,
+                    'k' => &mut castling_rights.black_king_side,
+                    'q' => &mut castling_rights.black_queen_side,
+                    _ => return Err(FenError::InvalidCastlingRights(fields[2].to_string())),
+                };
+                if *right {
+                    return Err(FenError::InvalidCastlingRights(fields[2].to_string()));
+                }
+                *right = true;
+            }
+        }
+
+        let en_passant = match fields[3] {
+            "-" => None,
+            text => Some(
+                Square::from_algebraic(text)
+                    .filter(|square| square.y == 2 || square.y == 5)
+                    .ok_or_else(|| FenError::InvalidEnPassant(text.to_string()))?,
+            ),
+        };
+
+        let halfmove_clock = match fields.get(4) {
+            Some(text) => text
+                .parse()
+                .map_err(|_| FenError::InvalidHalfmoveClock(text.to_string()))?,
+            None => 0,
+        };
+
+        let fullmove_number = match fields.get(5) {
+            Some(text) => text
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| FenError::InvalidFullmoveNumber(text.to_string()))?,
+            None => 1,
+        };
+
+        let mut board = Board {
+            pieces,
+            castling_rights,
+            en_passant,
+            side_to_move,
+            halfmove_clock,
+            fullmove_number,
+            position_history: Vec::new(),
+            hash: 0,
+            bitboards: Bitboards::default(),
+            undo_stack: Vec::new(),
+        };
+        board.sync_pieces();
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for (y, row) in self.pieces.iter().enumerate() {
+            let mut empty = 0;
+            for cell in row.iter() {
+                match cell {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push_str(&piece.to_string());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y < 7 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(match self.side_to_move {
+            PieceColor::White => 'w',
+            PieceColor::Black => 'b',
+        });
+
+        fen.push(' ');
+        let rights = [
+            (self.castling_rights.white_king_side, 'K'),
+            (self.castling_rights.white_queen_side, 'Q'),
+            (self.castling_rights.black_king_side, 'k'),
+            (self.castling_rights.black_queen_side, 'q'),
+        ];
+        if rights.iter().any(|&(allowed, _)| allowed) {
+            fen.extend(
+                rights
+                    .iter()
+                    .filter(|&&(allowed, _)| allowed)
+                    .map(|&(_, c)| c),
+            );
+        } else {
+            fen.push('-');
+        }
+
+        fen.push(' ');
+        match self.en_passant {
+            Some(square) => fen.push_str(&square.to_string()),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
+
+        fen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_fen() {
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 37",
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn fills_in_missing_move_counters() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - -").unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn rejects_wrong_field_count() {
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K3 w -").unwrap_err(),
+            FenError::WrongFieldCount(3)
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 extra").unwrap_err(),
+            FenError::WrongFieldCount(7)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_piece_placement() {
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/4K3 w - - 0 1").unwrap_err(),
+            FenError::WrongRankCount(7)
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/9/8/4K3 w - - 0 1").unwrap_err(),
+            FenError::InvalidPiece('9')
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/7/8/4K3 w - - 0 1").unwrap_err(),
+            FenError::WrongRankLength { rank: 3, length: 7 }
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K3p w - - 0 1").unwrap_err(),
+            FenError::WrongRankLength { rank: 1, length: 9 }
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4X3 w - - 0 1").unwrap_err(),
+            FenError::InvalidPiece('X')
+        );
+    }
+
+    #[test]
+    fn requires_exactly_one_king_per_side() {
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/8 w - - 0 1").unwrap_err(),
+            FenError::MissingKing(PieceColor::White)
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/3KK3 w - - 0 1").unwrap_err(),
+            FenError::MissingKing(PieceColor::White)
+        );
+        assert_eq!(
+            Board::from_fen("3kk3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap_err(),
+            FenError::MissingKing(PieceColor::Black)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_side_and_castling_rights() {
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1").unwrap_err(),
+            FenError::InvalidSideToMove("x".to_string())
+        );
+        assert_eq!(
+            Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KKq - 0 1").unwrap_err(),
+            FenError::InvalidCastlingRights("KKq".to_string())
+        );
+        assert_eq!(
+            Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQx - 0 1").unwrap_err(),
+            FenError::InvalidCastlingRights("KQx".to_string())
+        );
+    }
+
+    #[test]
+    fn en_passant_target_must_be_on_the_third_or_sixth_rank() {
+        assert_eq!(
+            Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d5 0 2").unwrap_err(),
+            FenError::InvalidEnPassant("d5".to_string())
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - z9 0 1").unwrap_err(),
+            FenError::InvalidEnPassant("z9".to_string())
+        );
+        assert!(Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_move_counters() {
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - x 1").unwrap_err(),
+            FenError::InvalidHalfmoveClock("x".to_string())
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0").unwrap_err(),
+            FenError::InvalidFullmoveNumber("0".to_string())
+        );
+    }
+}
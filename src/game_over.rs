@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+
+use crate::state::GameState;
+
+pub struct GameOverPlugin;
+
+#[derive(Component)]
+struct GameOverBanner;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, show_game_over.run_if(state_changed::<GameState>));
+    }
+}
+
+fn show_game_over(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    banner_query: Query<Entity, With<GameOverBanner>>,
+) {
+    for entity in banner_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let GameState::GameOver(result) = state.get() else {
+        return;
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            GameOverBanner,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        padding: UiRect::all(Val::Px(16.0)),
+                        ..default()
+                    },
+                    background_color: Color::srgba(0.0, 0.0, 0.0, 0.75).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        result.to_string(),
+                        TextStyle {
+                            font_size: 40.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
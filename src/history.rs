@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{board::Board, rules::Move};
+
+/// Every move played since `starting_position`, in order.
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MoveHistory {
+    pub starting_position: Board,
+    pub moves: Vec<Move>,
+}
+
+impl MoveHistory {
+    pub fn new(starting_position: Board) -> Self {
+        Self {
+            starting_position,
+            moves: Vec::new(),
+        }
+    }
+
+    /// Position reached after playing every recorded move.
+    pub fn current_position(&self) -> Board {
+        let mut board = self.starting_position.clone();
+        for &mv in self.moves.iter() {
+            board.make_move(mv);
+        }
+        board
+    }
+}
@@ -4,6 +4,30 @@ pub mod state;
 pub mod camera;
 pub mod piece;
 pub mod board;
+pub mod rules;
+pub mod promotion;
+pub mod fen;
+pub mod options;
+pub mod san;
+pub mod pgn;
+pub mod history;
+pub mod side_panel;
+pub mod move_list;
+pub mod network;
+pub mod lobby;
+pub mod protocol;
+pub mod server;
+pub mod chat;
+pub mod clock;
+pub mod actions;
+pub mod game_over;
+pub mod zobrist;
+pub mod bitboard;
+pub mod undo;
+pub mod perft;
+pub mod engine;
+pub mod ai;
+pub mod ai_menu;
 
 pub mod constants;
 pub mod resources;
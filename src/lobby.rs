@@ -0,0 +1,316 @@
+use bevy::prelude::*;
+
+use crate::{
+    clock::TimeControl,
+    network::NetworkConnection,
+    protocol::{ColorPreference, LiveGame, Message, Seek, SeekOptions},
+    state::GameState,
+    BG_COLOR, FONT_SIZE, SIDE_PANEL_WIDTH,
+};
+
+pub struct LobbyPlugin;
+
+/// Seeks currently open and games being played on the server.
+#[derive(Resource, Debug, Default)]
+pub struct Lobby {
+    pub seeks: Vec<Seek>,
+    pub games: Vec<LiveGame>,
+}
+
+/// Seek the player is about to create, `time_control` indexing
+/// `TimeControl::presets`.
+#[derive(Resource, Debug)]
+struct SeekForm {
+    time_control: usize,
+    color: ColorPreference,
+    rated: bool,
+}
+
+#[derive(Component)]
+struct LobbyRoot;
+
+#[derive(Component)]
+struct SeekList;
+
+#[derive(Component)]
+struct GameList;
+
+#[derive(Component, Clone, Copy)]
+enum LobbyButton {
+    TimeControl,
+    Color,
+    Rated,
+    Create,
+    Accept(u64),
+    Cancel,
+    Watch(u64),
+}
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Lobby>()
+            .insert_resource(SeekForm {
+                time_control: 2,
+                color: ColorPreference::Random,
+                rated: false,
+            })
+            .add_systems(
+                OnEnter(GameState::MainMenu),
+                (setup_lobby, (update_lobby_lists, update_form_labels))
+                    .chain()
+                    .run_if(resource_exists::<NetworkConnection>),
+            )
+            .add_systems(OnExit(GameState::MainMenu), despawn_lobby)
+            .add_systems(
+                Update,
+                (
+                    handle_lobby_buttons.run_if(resource_exists::<NetworkConnection>),
+                    update_lobby_lists.run_if(resource_changed::<Lobby>),
+                    update_form_labels.run_if(resource_changed::<SeekForm>),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::MainMenu)),
+            );
+    }
+}
+
+impl SeekForm {
+    fn options(&self) -> SeekOptions {
+        SeekOptions {
+            time_control: TimeControl::presets()[self.time_control].clone(),
+            color: self.color,
+            rated: self.rated,
+        }
+    }
+}
+
+fn text_style() -> TextStyle {
+    TextStyle {
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+        ..default()
+    }
+}
+
+fn spawn_button(parent: &mut ChildBuilder, label: impl Into<String>, button: LobbyButton) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(10.0), Val::Px(6.0)),
+                    ..default()
+                },
+                background_color: Color::srgb(0.25, 0.25, 0.3).into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(label, text_style()));
+        });
+}
+
+fn setup_lobby(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    bottom: Val::Px(0.0),
+                    right: Val::Px(SIDE_PANEL_WIDTH),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(24.0)),
+                    row_gap: Val::Px(12.0),
+                    ..default()
+                },
+                background_color: Color::srgb_u8(BG_COLOR.0, BG_COLOR.1, BG_COLOR.2).into(),
+                ..default()
+            },
+            LobbyRoot,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Lobby",
+                TextStyle {
+                    font_size: 40.0,
+                    ..text_style()
+                },
+            ));
+
+            parent.spawn(TextBundle::from_section("Open seeks", text_style()));
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                SeekList,
+            ));
+
+            parent.spawn(TextBundle::from_section("Live games", text_style()));
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                GameList,
+            ));
+
+            parent.spawn(TextBundle::from_section("New seek", text_style()));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(8.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_button(parent, "", LobbyButton::TimeControl);
+                    spawn_button(parent, "", LobbyButton::Color);
+                    spawn_button(parent, "", LobbyButton::Rated);
+                    spawn_button(parent, "Create seek", LobbyButton::Create);
+                });
+        });
+}
+
+fn despawn_lobby(mut commands: Commands, root_query: Query<Entity, With<LobbyRoot>>) {
+    for entity in root_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn handle_lobby_buttons(
+    button_query: Query<(&Interaction, &LobbyButton), Changed<Interaction>>,
+    mut form: ResMut<SeekForm>,
+    mut connection: ResMut<NetworkConnection>,
+) {
+    for (interaction, &button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            LobbyButton::TimeControl => {
+                form.time_control = (form.time_control + 1) % TimeControl::presets().len();
+            }
+            LobbyButton::Color => {
+                form.color = match form.color {
+                    ColorPreference::Random => ColorPreference::White,
+                    ColorPreference::White => ColorPreference::Black,
+                    ColorPreference::Black => ColorPreference::Random,
+                };
+            }
+            LobbyButton::Rated => form.rated = !form.rated,
+            LobbyButton::Create => connection.send(&Message::CreateSeek {
+                options: form.options(),
+            }),
+            LobbyButton::Accept(id) => connection.send(&Message::AcceptSeek { id }),
+            LobbyButton::Cancel => connection.send(&Message::CancelSeek),
+            LobbyButton::Watch(id) => connection.send(&Message::Watch { id }),
+        }
+    }
+}
+
+fn spawn_placeholder(parent: &mut ChildBuilder, text: &str) {
+    parent.spawn(TextBundle::from_section(
+        text,
+        TextStyle {
+            color: Color::srgb(0.6, 0.6, 0.6),
+            ..text_style()
+        },
+    ));
+}
+
+fn spawn_row(parent: &mut ChildBuilder, text: String, label: &str, button: LobbyButton) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(12.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(text, text_style()));
+            spawn_button(parent, label, button);
+        });
+}
+
+fn update_lobby_lists(
+    mut commands: Commands,
+    lobby: Res<Lobby>,
+    seek_list_query: Query<Entity, With<SeekList>>,
+    game_list_query: Query<Entity, With<GameList>>,
+) {
+    if let Ok(list) = seek_list_query.get_single() {
+        commands.entity(list).despawn_descendants();
+        commands.entity(list).with_children(|parent| {
+            if lobby.seeks.is_empty() {
+                spawn_placeholder(parent, "No open seeks yet");
+            }
+
+            for seek in lobby.seeks.iter() {
+                let text = format!("#{}  {}", seek.id, seek.options);
+                if seek.mine {
+                    spawn_row(parent, text, "Cancel", LobbyButton::Cancel);
+                } else {
+                    spawn_row(parent, text, "Accept", LobbyButton::Accept(seek.id));
+                }
+            }
+        });
+    }
+
+    if let Ok(list) = game_list_query.get_single() {
+        commands.entity(list).despawn_descendants();
+        commands.entity(list).with_children(|parent| {
+            if lobby.games.is_empty() {
+                spawn_placeholder(parent, "No games being played");
+            }
+
+            for game in lobby.games.iter() {
+                let text = format!(
+                    "Game {}  {}  {}  {} watching",
+                    game.id,
+                    game.time_control,
+                    if game.rated { "Rated" } else { "Casual" },
+                    game.spectators
+                );
+                spawn_row(parent, text, "Watch", LobbyButton::Watch(game.id));
+            }
+        });
+    }
+}
+
+fn update_form_labels(
+    form: Res<SeekForm>,
+    button_query: Query<(&LobbyButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (button, children) in button_query.iter() {
+        let label = match button {
+            LobbyButton::TimeControl => {
+                format!("Time: {}", TimeControl::presets()[form.time_control])
+            }
+            LobbyButton::Color => format!("Color: {}", form.color),
+            LobbyButton::Rated => (if form.rated { "Rated" } else { "Casual" }).to_string(),
+            _ => continue,
+        };
+
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
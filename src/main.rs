@@ -1,16 +1,43 @@
+use std::process;
+
 use bevy::prelude::*;
 use bevy_multiplayer_chess::{
-    board::BoardPlugin, camera::MyCameraPlugin, close_on_esc::CloseOnEscapePlugin, default_plugins::MyDefaultPlugins, piece::PiecePlugin, resources::ResourcesPlugin, state::GameState
+    actions::ActionsPlugin, ai::AiPlugin, ai_menu::AiMenuPlugin, board::BoardPlugin, camera::MyCameraPlugin, chat::ChatPlugin, clock::ClockPlugin, close_on_esc::CloseOnEscapePlugin, default_plugins::MyDefaultPlugins, game_over::GameOverPlugin, lobby::LobbyPlugin, move_list::MoveListPlugin, network::NetworkPlugin, options::{LaunchOptions, USAGE}, perft::print_perft_divide, pgn::PgnPlugin, piece::PiecePlugin, promotion::PromotionPlugin, resources::ResourcesPlugin, side_panel::SidePanelPlugin, state::GameState, undo::UndoPlugin
 };
 
 fn main() {
+    let options = LaunchOptions::from_args(std::env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
+
+    if let Some(depth) = options.perft {
+        let board = options.game.clone().unwrap_or_default().current_position();
+        print_perft_divide(&board, depth);
+        return;
+    }
+
     App::new()
+        .insert_resource(options)
         .add_plugins(MyDefaultPlugins)
         .add_plugins(CloseOnEscapePlugin)
         .add_plugins(MyCameraPlugin)
         .add_plugins(ResourcesPlugin)
         .add_plugins(BoardPlugin)
         .add_plugins(PiecePlugin)
+        .add_plugins(PromotionPlugin)
+        .add_plugins(GameOverPlugin)
+        .add_plugins(PgnPlugin)
+        .add_plugins(SidePanelPlugin)
+        .add_plugins(MoveListPlugin)
+        .add_plugins(NetworkPlugin)
+        .add_plugins(LobbyPlugin)
+        .add_plugins(ChatPlugin)
+        .add_plugins(ClockPlugin)
+        .add_plugins(ActionsPlugin)
+        .add_plugins(UndoPlugin)
+        .add_plugins(AiPlugin)
+        .add_plugins(AiMenuPlugin)
         .init_state::<GameState>()
         .run();
 }
@@ -0,0 +1,176 @@
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+};
+
+use crate::{
+    board::Board,
+    chat::chat_focused,
+    history::MoveHistory,
+    piece::{LocalPlayer, MoveRequest, MoveSet, PieceColor},
+    promotion::PendingPromotion,
+    side_panel::{setup_side_panel, SidePanel},
+    state::GameState,
+    FONT_SIZE,
+};
+
+pub struct MoveListPlugin;
+
+/// Move typed on the keyboard in SAN, played on `Enter`.
+#[derive(Resource, Default)]
+pub struct MoveInput {
+    pub text: String,
+    pub error: Option<String>,
+}
+
+#[derive(Component)]
+struct MoveListText;
+
+#[derive(Component)]
+struct MoveInputText;
+
+const MAX_LISTED_MOVES: usize = 20;
+
+const MAX_INPUT_LENGTH: usize = 12;
+
+impl Plugin for MoveListPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MoveInput>()
+            .add_systems(Startup, setup_move_list.after(setup_side_panel))
+            .add_systems(
+                Update,
+                (
+                    update_move_list.run_if(resource_exists_and_changed::<MoveHistory>),
+                    type_move.in_set(MoveSet::Input).run_if(
+                        in_state(GameState::InGame)
+                            .and_then(not(resource_exists::<PendingPromotion>))
+                            .and_then(not(chat_focused)),
+                    ),
+                    update_move_input.run_if(resource_changed::<MoveInput>),
+                ),
+            );
+    }
+}
+
+fn setup_move_list(mut commands: Commands, panel_query: Query<Entity, With<SidePanel>>) {
+    let Ok(panel) = panel_query.get_single() else {
+        return;
+    };
+
+    commands.entity(panel).with_children(|parent| {
+        parent.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: FONT_SIZE,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            MoveListText,
+        ));
+        parent.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: FONT_SIZE,
+                    color: Color::srgb(0.8, 0.8, 0.6),
+                    ..default()
+                },
+            ),
+            MoveInputText,
+        ));
+    });
+}
+
+fn update_move_list(
+    history: Res<MoveHistory>,
+    mut text_query: Query<&mut Text, With<MoveListText>>,
+) {
+    let mut lines = Vec::new();
+    let mut board = history.starting_position.clone();
+
+    for &mv in history.moves.iter() {
+        let san = board.move_to_san_with_en_passant(mv);
+
+        match board.side_to_move {
+            PieceColor::White => lines.push(format!("{}. {}", board.fullmove_number, san)),
+            PieceColor::Black => match lines.last_mut() {
+                Some(line) => line.push_str(&format!("  {}", san)),
+                _ => lines.push(format!("{}... {}", board.fullmove_number, san)),
+            },
+        }
+
+        board.make_move(mv);
+    }
+
+    let first = lines.len().saturating_sub(MAX_LISTED_MOVES);
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = lines[first..].join("\n");
+    }
+}
+
+fn type_move(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut move_input: ResMut<MoveInput>,
+    board: Res<Board>,
+    local_player: Res<LocalPlayer>,
+    mut move_requests: EventWriter<MoveRequest>,
+) {
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        keyboard_events.clear();
+        return;
+    }
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        match &event.logical_key {
+            Key::Character(text) if move_input.text.len() + text.len() <= MAX_INPUT_LENGTH => {
+                move_input.text.push_str(text);
+                move_input.error = None;
+            }
+            Key::Backspace => {
+                move_input.text.pop();
+                move_input.error = None;
+            }
+            Key::Enter if !move_input.text.trim().is_empty() => {
+                let text = std::mem::take(&mut move_input.text);
+
+                if !local_player.controls(board.side_to_move) {
+                    move_input.error = Some(format!("{} is not yours to move", board.side_to_move));
+                    continue;
+                }
+
+                match board.parse_san(&text) {
+                    Ok(mv) => {
+                        move_requests.send(MoveRequest(mv));
+                    }
+                    Err(error) => move_input.error = Some(error.to_string()),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_move_input(
+    move_input: Res<MoveInput>,
+    mut text_query: Query<&mut Text, With<MoveInputText>>,
+) {
+    let value = match &move_input.error {
+        Some(error) => error.clone(),
+        None if move_input.text.is_empty() => "Type a move and press Enter".to_string(),
+        None => format!("> {}", move_input.text),
+    };
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
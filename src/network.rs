@@ -0,0 +1,630 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    actions::{take_back, PendingOffers},
+    board::{Board, BoardConfiguration, PieceEntity, RedrawBoard},
+    chat::{chat_focused, Chat},
+    clock::GameClock,
+    history::MoveHistory,
+    lobby::Lobby,
+    options::LaunchOptions,
+    piece::{play_move, LocalPlayer, MoveRequest, MoveSet, PieceColor},
+    protocol::{check_version, ChatChannel, Message, PROTOCOL_VERSION},
+    server::Server,
+    side_panel::{setup_side_panel, SidePanel},
+    state::{game_over, GameState},
+    NextGame, CONNECT_TIMEOUT_SECS, DEFAULT_PORT, FONT_SIZE, RECONNECT_INTERVAL_SECS,
+};
+
+pub struct NetworkPlugin;
+
+/// How this instance takes part in a network game. Hosting runs a server
+/// inside this instance and joins it like any other player would.
+#[derive(Debug, Clone)]
+pub enum NetworkRole {
+    Host(String),
+    Connect(String),
+}
+
+enum NetworkEvent {
+    Connected(TcpStream),
+    ConnectionFailed(String),
+    Received(Message),
+    Disconnected,
+    Error(String),
+}
+
+/// Connection to the game server. The server owns the position: moves are
+/// only played here once it has accepted them.
+#[derive(Resource)]
+pub struct NetworkConnection {
+    pub color: Option<PieceColor>,
+    pub status: String,
+    address: String,
+    stream: Option<TcpStream>,
+    /// Token of the game being played, to resume it after a disconnection.
+    session: Option<String>,
+    /// Game followed as a spectator.
+    watching: Option<u64>,
+    resuming: bool,
+    /// Running while trying to get back to the server after losing the
+    /// connection mid-game.
+    reconnect: Option<Timer>,
+    /// Whether a connection attempt is under way, so that no other one is
+    /// started until it succeeds or fails.
+    connecting: bool,
+    /// Set when this side closed the connection on purpose, so that losing
+    /// it does not start a reconnection.
+    hung_up: bool,
+    events: Mutex<Receiver<NetworkEvent>>,
+    events_sender: Sender<NetworkEvent>,
+    /// Game messages received before the board was ready for them.
+    pending: Vec<Message>,
+}
+
+#[derive(Component)]
+struct NetworkStatusText;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Startup,
+            (start_network, setup_network_status.after(setup_side_panel)).chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                reconnect,
+                receive_messages,
+                (send_move_requests, play_server_messages)
+                    .in_set(MoveSet::Apply)
+                    .run_if(in_state(GameState::InGame).or_else(game_over)),
+                return_to_lobby.run_if(
+                    (in_state(GameState::InGame).or_else(game_over)).and_then(not(chat_focused)),
+                ),
+                update_network_status.run_if(resource_changed::<NetworkConnection>),
+            )
+                .chain()
+                .run_if(resource_exists::<NetworkConnection>),
+        );
+    }
+}
+
+impl NetworkRole {
+    /// Address to listen on or connect to, `DEFAULT_PORT` being used when
+    /// only a host or only a port is given.
+    pub fn address(&self) -> String {
+        match self {
+            NetworkRole::Host(address) if address.parse::<u16>().is_ok() => {
+                format!("0.0.0.0:{}", address)
+            }
+            NetworkRole::Host(address) | NetworkRole::Connect(address) => {
+                if address.contains(':') {
+                    address.clone()
+                } else {
+                    format!("{}:{}", address, DEFAULT_PORT)
+                }
+            }
+        }
+    }
+}
+
+impl NetworkConnection {
+    pub fn is_watching(&self) -> bool {
+        self.watching.is_some()
+    }
+
+    fn disconnect(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn hang_up(&mut self) {
+        self.hung_up = true;
+        self.disconnect();
+    }
+
+    pub fn send(&mut self, message: &Message) {
+        let Some(stream) = self.stream.as_mut() else {
+            return;
+        };
+
+        if let Err(error) = writeln!(stream, "{}", message.encode()) {
+            self.status = format!("Connection lost: {}", error);
+            self.disconnect();
+        }
+    }
+}
+
+fn start_network(
+    mut commands: Commands,
+    options: Res<LaunchOptions>,
+    mut local_player: ResMut<LocalPlayer>,
+) {
+    let Some(role) = options.network.clone() else {
+        return;
+    };
+
+    let (events_sender, events) = mpsc::channel();
+
+    let (address, status, connecting) = match role {
+        NetworkRole::Host(_) => match TcpListener::bind(role.address()) {
+            Ok(listener) => {
+                let port = listener
+                    .local_addr()
+                    .map_or(DEFAULT_PORT, |address| address.port());
+                let server = Server::new(options.game.clone().unwrap_or_default());
+                thread::spawn(move || server.serve(listener));
+
+                let address = format!("127.0.0.1:{}", port);
+                connect(address.clone(), events_sender.clone());
+                (address, format!("Hosting on {}", role.address()), true)
+            }
+            Err(error) => (
+                String::new(),
+                format!("Cannot host on {}: {}", role.address(), error),
+                false,
+            ),
+        },
+        NetworkRole::Connect(_) => {
+            connect(role.address(), events_sender.clone());
+            (
+                role.address(),
+                format!("Connecting to {}", role.address()),
+                true,
+            )
+        }
+    };
+
+    *local_player = LocalPlayer::none();
+
+    commands.insert_resource(NetworkConnection {
+        color: None,
+        status,
+        address,
+        stream: None,
+        session: None,
+        watching: None,
+        resuming: false,
+        reconnect: None,
+        connecting,
+        hung_up: false,
+        events: Mutex::new(events),
+        events_sender,
+        pending: Vec::new(),
+    });
+}
+
+fn connect(address: String, sender: Sender<NetworkEvent>) {
+    thread::spawn(move || {
+        let event = open_stream(&address)
+            .map(NetworkEvent::Connected)
+            .unwrap_or_else(|error| NetworkEvent::ConnectionFailed(error.to_string()));
+        let _ = sender.send(event);
+    });
+}
+
+/// Connects to the first of the addresses `address` resolves to that
+/// answers within `CONNECT_TIMEOUT_SECS`.
+fn open_stream(address: &str) -> io::Result<TcpStream> {
+    let timeout = Duration::from_secs(CONNECT_TIMEOUT_SECS);
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "unknown address");
+
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last_error = error,
+        }
+    }
+
+    Err(last_error)
+}
+
+fn spawn_reader(stream: &TcpStream, sender: Sender<NetworkEvent>) -> std::io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            let event = match Message::decode(&line) {
+                Ok(message) => NetworkEvent::Received(message),
+                Err(error) => NetworkEvent::Error(error.to_string()),
+            };
+            if sender.send(event).is_err() {
+                return;
+            }
+        }
+
+        let _ = sender.send(NetworkEvent::Disconnected);
+    });
+
+    Ok(())
+}
+
+fn send_move_requests(
+    mut connection: ResMut<NetworkConnection>,
+    mut move_requests: EventReader<MoveRequest>,
+    board: Res<Board>,
+    local_player: Res<LocalPlayer>,
+) {
+    for &MoveRequest(mv) in move_requests.read() {
+        if local_player.controls(board.side_to_move) && board.is_legal_move(mv) {
+            connection.send(&Message::Move { mv });
+        }
+    }
+}
+
+/// Handles what the server sends. Lobby and connection messages are dealt
+/// with right away while game messages wait in `pending` until the board
+/// they apply to is set up.
+fn receive_messages(
+    mut commands: Commands,
+    mut connection: ResMut<NetworkConnection>,
+    mut lobby: ResMut<Lobby>,
+    mut chat: ResMut<Chat>,
+    mut local_player: ResMut<LocalPlayer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let events: Vec<NetworkEvent> = connection.events.lock().unwrap().try_iter().collect();
+
+    for event in events {
+        match event {
+            NetworkEvent::Connected(stream) => {
+                connection.connecting = false;
+                if let Err(error) = spawn_reader(&stream, connection.events_sender.clone()) {
+                    connection.status = format!("Connection failed: {}", error);
+                    continue;
+                }
+                connection.stream = Some(stream);
+                connection.hung_up = false;
+                connection.reconnect = None;
+                connection.status = "Connected".to_string();
+                connection.send(&Message::Hello {
+                    version: PROTOCOL_VERSION,
+                });
+            }
+            NetworkEvent::Received(Message::Hello { version }) => match check_version(version) {
+                Ok(()) => match connection.session.clone() {
+                    Some(token) => {
+                        connection.resuming = true;
+                        connection.status = "Resuming the game".to_string();
+                        connection.send(&Message::Resume { token });
+                    }
+                    None => connection.status = "Connected to the lobby".to_string(),
+                },
+                Err(error) => {
+                    connection.hang_up();
+                    connection.status = format!("Cannot play on this server: {}", error);
+                }
+            },
+            NetworkEvent::Received(Message::Lobby { seeks, games }) => {
+                lobby.seeks = seeks;
+                lobby.games = games;
+            }
+            NetworkEvent::Received(Message::Start {
+                color,
+                time_control,
+                rated,
+                token,
+                history,
+            }) => {
+                connection.color = Some(color);
+                connection.session = Some(token);
+                connection.resuming = false;
+                connection.pending.clear();
+                connection.status = format!(
+                    "You play {} ({} {})",
+                    color,
+                    time_control,
+                    if rated { "rated" } else { "casual" }
+                );
+                chat.channel = ChatChannel::Game;
+                *local_player = LocalPlayer::only(color);
+                commands.insert_resource(GameClock::new(time_control));
+                commands.insert_resource(NextGame(*history));
+                next_state.set(GameState::GameInitResources);
+            }
+            NetworkEvent::Received(Message::Spectate {
+                id,
+                time_control,
+                rated,
+                history,
+            }) => {
+                connection.watching = Some(id);
+                connection.pending.clear();
+                connection.status = format!(
+                    "Watching game {} ({} {}), press Enter to leave",
+                    id,
+                    time_control,
+                    if rated { "rated" } else { "casual" }
+                );
+                *local_player = LocalPlayer::none();
+                commands.insert_resource(GameClock::new(time_control));
+                commands.insert_resource(NextGame(*history));
+                next_state.set(GameState::GameInitResources);
+            }
+            NetworkEvent::Received(Message::Ping { id }) => {
+                connection.send(&Message::Pong { id });
+            }
+            NetworkEvent::Received(Message::Chat {
+                channel,
+                from,
+                text,
+                mine,
+            }) => chat.receive(channel, from, text, mine),
+            NetworkEvent::Received(Message::OpponentDisconnected { grace_secs }) => {
+                connection.status = format!(
+                    "Opponent disconnected, waiting {} s for them to come back",
+                    grace_secs
+                );
+            }
+            NetworkEvent::Received(Message::OpponentReconnected) => {
+                connection.status = "Opponent reconnected".to_string();
+            }
+            NetworkEvent::Received(Message::Error { message }) if connection.resuming => {
+                connection.resuming = false;
+                connection.session = None;
+                connection.color = None;
+                connection.status = format!("Could not resume the game: {}", message);
+                next_state.set(GameState::MainMenu);
+            }
+            NetworkEvent::Received(Message::Error { message }) => {
+                connection.status = message;
+            }
+            NetworkEvent::Received(message) => connection.pending.push(message),
+            NetworkEvent::Disconnected => {
+                *local_player = LocalPlayer::none();
+                lobby.seeks.clear();
+                lobby.games.clear();
+                connection.watching = None;
+                if connection.hung_up {
+                    continue;
+                }
+
+                connection.disconnect();
+                if connection.session.is_some() {
+                    connection.status = "Connection lost, reconnecting".to_string();
+                    connection.reconnect = Some(Timer::from_seconds(
+                        RECONNECT_INTERVAL_SECS,
+                        TimerMode::Repeating,
+                    ));
+                } else {
+                    connection.status = "Disconnected from the server".to_string();
+                }
+            }
+            NetworkEvent::ConnectionFailed(error) => {
+                connection.connecting = false;
+                connection.status = if connection.reconnect.is_some() {
+                    format!("Connection lost, reconnecting ({})", error)
+                } else {
+                    format!("Cannot connect to the server: {}", error)
+                };
+            }
+            NetworkEvent::Error(error) => {
+                connection.status = format!("Network error: {}", error);
+            }
+        }
+    }
+}
+
+/// Plays the game messages received from the server on the board.
+#[allow(clippy::too_many_arguments)]
+fn play_server_messages(
+    mut commands: Commands,
+    mut connection: ResMut<NetworkConnection>,
+    board_config: Res<BoardConfiguration>,
+    mut board: ResMut<Board>,
+    mut history: ResMut<MoveHistory>,
+    mut local_player: ResMut<LocalPlayer>,
+    mut game_clock: Option<ResMut<GameClock>>,
+    mut offers: ResMut<PendingOffers>,
+    mut redraw_events: EventWriter<RedrawBoard>,
+    mut piece_query: Query<(Entity, &mut Transform, &mut TextureAtlas), With<PieceEntity>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if connection.pending.is_empty() {
+        return;
+    }
+
+    for message in std::mem::take(&mut connection.pending) {
+        match message {
+            Message::Move { mv } => {
+                if !board.is_legal_move(mv) {
+                    connection.status = format!("Server sent an illegal move: {}", mv);
+                    continue;
+                }
+
+                play_move(
+                    &mut commands,
+                    &board_config,
+                    &mut board,
+                    mv,
+                    &mut piece_query,
+                    &mut history,
+                    &mut next_state,
+                );
+            }
+            Message::DrawOffer => offers.draw = connection.color.map(|color| color.opponent()),
+            Message::DeclineDraw => {
+                offers.draw = None;
+                connection.status = "Your opponent declined the draw".to_string();
+            }
+            Message::Takeback => {
+                offers.takeback = connection.color.map(|color| color.opponent());
+            }
+            Message::DeclineTakeback => {
+                offers.takeback = None;
+                connection.status = "Your opponent declined the takeback".to_string();
+            }
+            Message::TakenBack { plies } => {
+                if plies > history.moves.len() {
+                    connection.status = "Server took back more moves than played".to_string();
+                    continue;
+                }
+                take_back(plies, &mut board, &mut history, &mut redraw_events);
+            }
+            Message::GameEnd { result } => {
+                *local_player = LocalPlayer::none();
+                connection.color = None;
+                connection.session = None;
+                connection.watching = None;
+                connection.status = "Press Enter to go back to the lobby".to_string();
+                next_state.set(GameState::GameOver(result));
+            }
+            Message::ClockSync {
+                white_ms,
+                black_ms,
+                running,
+            } => {
+                if let Some(game_clock) = game_clock.as_mut() {
+                    game_clock.clock.sync(
+                        Duration::from_millis(white_ms),
+                        Duration::from_millis(black_ms),
+                        running,
+                    );
+                }
+            }
+            _ => connection.status = "Unexpected message from the server".to_string(),
+        }
+    }
+}
+
+/// Tries to connect again every `RECONNECT_INTERVAL_SECS` after losing the
+/// connection mid-game, one attempt at a time.
+fn reconnect(time: Res<Time>, mut connection: ResMut<NetworkConnection>) {
+    let Some(timer) = connection.reconnect.as_mut() else {
+        return;
+    };
+
+    if timer.tick(time.delta()).just_finished() && !connection.connecting {
+        connection.connecting = true;
+        connect(connection.address.clone(), connection.events_sender.clone());
+    }
+}
+
+/// Goes back to the lobby on Enter once the game is over, or right away when
+/// only watching it.
+fn return_to_lobby(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut connection: ResMut<NetworkConnection>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Enter) {
+        return;
+    }
+
+    if *state.get() == GameState::InGame {
+        if connection.watching.take().is_none() {
+            return;
+        }
+        connection.send(&Message::StopWatching);
+        connection.status = "Connected to the lobby".to_string();
+    }
+    next_state.set(GameState::MainMenu);
+}
+
+pub fn setup_network_status(
+    mut commands: Commands,
+    connection: Option<Res<NetworkConnection>>,
+    panel_query: Query<Entity, With<SidePanel>>,
+) {
+    let (Some(connection), Ok(panel)) = (connection, panel_query.get_single()) else {
+        return;
+    };
+
+    commands.entity(panel).with_children(|parent| {
+        parent.spawn((
+            TextBundle::from_section(
+                connection.status.clone(),
+                TextStyle {
+                    font_size: FONT_SIZE,
+                    color: Color::srgb(0.6, 0.8, 1.0),
+                    ..default()
+                },
+            ),
+            NetworkStatusText,
+        ));
+    });
+}
+
+fn update_network_status(
+    connection: Res<NetworkConnection>,
+    mut text_query: Query<&mut Text, With<NetworkStatusText>>,
+) {
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = connection.status.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn failed_writes_start_a_reconnection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server_end, _) = listener.accept().unwrap();
+        let (events_sender, events) = mpsc::channel();
+        spawn_reader(&stream, events_sender.clone()).unwrap();
+
+        let mut connection = NetworkConnection {
+            color: Some(PieceColor::White),
+            status: String::new(),
+            address: listener.local_addr().unwrap().to_string(),
+            stream: Some(stream),
+            session: Some("token".to_string()),
+            watching: None,
+            resuming: false,
+            reconnect: None,
+            connecting: false,
+            hung_up: false,
+            events: Mutex::new(events),
+            events_sender,
+            pending: Vec::new(),
+        };
+
+        drop(server_end);
+        for _ in 0..100 {
+            connection.send(&Message::CancelSeek);
+            if connection.stream.is_none() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(connection.stream.is_none(), "writes kept succeeding");
+
+        let mut app = App::new();
+        app.insert_resource(connection)
+            .init_resource::<Lobby>()
+            .init_resource::<Chat>()
+            .init_resource::<LocalPlayer>()
+            .init_resource::<NextState<GameState>>();
+        for _ in 0..100 {
+            app.world_mut().run_system_once(receive_messages);
+            let connection = app.world().resource::<NetworkConnection>();
+            if connection.reconnect.is_some() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no reconnection after losing the server");
+    }
+}
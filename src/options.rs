@@ -0,0 +1,86 @@
+use std::fs;
+
+use bevy::prelude::*;
+
+use crate::{
+    board::Board, clock::TimeControl, history::MoveHistory, network::NetworkRole, pgn::parse_pgn,
+};
+
+pub const USAGE: &str = "usage: bevy_multiplayer_chess [--fen \"<FEN>\" | --pgn <file>] \
+                         [--clock <time control>] \
+                         [--host [<address>:]<port> | --connect <address>[:<port>]] \
+                         [--computer] [--perft <depth>]";
+
+/// Settings given on the command line when launching the game.
+#[derive(Resource, Default, Clone)]
+pub struct LaunchOptions {
+    /// Game to resume instead of starting from the initial position.
+    pub game: Option<MoveHistory>,
+    /// Time control of local games, such as `5+3` or `40/90, 30+30`.
+    pub clock: Option<TimeControl>,
+    pub network: Option<NetworkRole>,
+    /// Skips the main menu and starts a game against the built-in engine at
+    /// its default level, the player taking White.
+    pub computer: bool,
+    /// Depth to run `perft divide` at on the position given, or the initial
+    /// one, instead of opening the game.
+    pub perft: Option<u32>,
+}
+
+impl LaunchOptions {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = LaunchOptions::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--fen" => {
+                    let fen = args.next().ok_or("--fen expects a FEN string")?;
+                    let board = Board::from_fen(&fen)
+                        .map_err(|error| format!("invalid FEN \"{}\": {}", fen, error))?;
+                    options.game = Some(MoveHistory::new(board));
+                }
+                "--pgn" => {
+                    let path = args.next().ok_or("--pgn expects a file path")?;
+                    let text = fs::read_to_string(&path)
+                        .map_err(|error| format!("cannot read {}: {}", path, error))?;
+                    let game = parse_pgn(&text)
+                        .map_err(|error| format!("invalid PGN in {}: {}", path, error))?
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| format!("no game found in {}", path))?;
+                    options.game = Some(MoveHistory {
+                        starting_position: game.starting_position,
+                        moves: game.moves,
+                    });
+                }
+                "--clock" => {
+                    let text = args.next().ok_or("--clock expects a time control")?;
+                    options.clock = Some(text.parse()?);
+                }
+                "--host" => {
+                    let address = args.next().ok_or("--host expects a port to listen on")?;
+                    options.network = Some(NetworkRole::Host(address));
+                }
+                "--connect" => {
+                    let address = args.next().ok_or("--connect expects an address")?;
+                    options.network = Some(NetworkRole::Connect(address));
+                }
+                "--computer" => options.computer = true,
+                "--perft" => {
+                    let text = args.next().ok_or("--perft expects a depth")?;
+                    let depth = text
+                        .parse()
+                        .map_err(|_| format!("'{}' is not a valid perft depth", text))?;
+                    options.perft = Some(depth);
+                }
+                other => return Err(format!("unknown argument '{}'", other)),
+            }
+        }
+
+        if options.computer && options.network.is_some() {
+            return Err("--computer cannot be used in network games".to_string());
+        }
+
+        Ok(options)
+    }
+}
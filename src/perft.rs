@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use crate::{board::Board, rules::Move};
+
+/// Number of move sequences `depth` plies long from `board`, the standard
+/// count for checking move generation against known results.
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    count_leaves(&mut board.clone(), depth)
+}
+
+/// Perft of `depth` split by first move, to narrow down which move a wrong
+/// total comes from.
+pub fn perft_divide(board: &Board, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+
+    let mut board = board.clone();
+    board
+        .legal_moves(board.side_to_move)
+        .into_iter()
+        .map(|mv| {
+            board.make_move(mv);
+            let nodes = count_leaves(&mut board, depth - 1);
+            board.unmake_move();
+            (mv, nodes)
+        })
+        .collect()
+}
+
+/// Walks the move tree with `make_move` and `unmake_move`, leaving `board`
+/// as it found it. Debug builds also check the incrementally updated hash
+/// against one computed from scratch in every position it walks into, so that
+/// the perft tests cover captures, castling, en passant and promotions for
+/// both.
+fn count_leaves(board: &mut Board, depth: u32) -> u64 {
+    debug_assert_eq!(
+        board.hash,
+        board.zobrist_hash(),
+        "hash out of date in {}",
+        board.to_fen()
+    );
+
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = board.legal_moves(board.side_to_move);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mv in moves {
+        board.make_move(mv);
+        nodes += count_leaves(board, depth - 1);
+        board.unmake_move();
+    }
+    nodes
+}
+
+/// Prints `perft_divide` of `board` one move per line in UCI notation,
+/// followed by the total and the time it took, the way engines do.
+pub fn print_perft_divide(board: &Board, depth: u32) {
+    let start = Instant::now();
+    let divide = perft_divide(board, depth);
+    let elapsed = start.elapsed();
+
+    for (mv, nodes) in divide.iter() {
+        println!("{}: {}", mv, nodes);
+    }
+
+    let total: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+    println!();
+    println!("Nodes searched: {}", total);
+    println!(
+        "Time: {} ms ({} nodes/s)",
+        elapsed.as_millis(),
+        nodes_per_second(total, elapsed)
+    );
+}
+
+fn nodes_per_second(nodes: u64, elapsed: Duration) -> u64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        (nodes as f64 / secs) as u64
+    } else {
+        0
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    error::Error,
+    fmt, fs,
+    iter::Peekable,
+    str::Chars,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    board::Board,
+    fen::{FenError, STARTING_FEN},
+    history::MoveHistory,
+    piece::PieceColor,
+    rules::Move,
+    san::SanError,
+    state::{game_over, GameResult, GameState},
+    PGN_EXPORT_PATH,
+};
+
+pub struct PgnPlugin;
+
+/// A game read from PGN, keeping the main line only.
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub starting_position: Board,
+    pub moves: Vec<Move>,
+    pub result: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    UnterminatedTag,
+    UnterminatedComment,
+    UnbalancedVariation,
+    InvalidFen(FenError),
+    InvalidMove { ply: usize, error: SanError },
+}
+
+enum Token {
+    Tag(String, String),
+    Move(String),
+    Result(String),
+}
+
+const RESULT_TOKENS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+const MAX_LINE_LENGTH: usize = 80;
+
+impl Plugin for PgnPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            save_pgn.run_if(in_state(GameState::InGame).or_else(game_over)),
+        );
+    }
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::UnterminatedTag => write!(f, "tag pair is missing its closing ']'"),
+            PgnError::UnterminatedComment => write!(f, "comment is missing its closing '}}'"),
+            PgnError::UnbalancedVariation => write!(f, "unbalanced parentheses around a variation"),
+            PgnError::InvalidFen(error) => write!(f, "invalid FEN tag: {}", error),
+            PgnError::InvalidMove { ply, error } => write!(f, "move {}: {}", ply / 2 + 1, error),
+        }
+    }
+}
+
+impl Error for PgnError {}
+
+pub fn result_token(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult::WhiteWins(_)) => "1-0",
+        Some(GameResult::BlackWins(_)) => "0-1",
+        Some(GameResult::Draw(_)) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+/// Exports the recorded game with the Seven Tag Roster, plus the `SetUp` and
+/// `FEN` tags when it did not start from the initial position.
+pub fn export_pgn(history: &MoveHistory, result: Option<GameResult>) -> String {
+    let result = result_token(result);
+    let start_fen = history.starting_position.to_fen();
+
+    let mut tags = vec![
+        ("Event", "Casual game".to_string()),
+        ("Site", "bevy_multiplayer_chess".to_string()),
+        ("Date", today()),
+        ("Round", "-".to_string()),
+        ("White", "?".to_string()),
+        ("Black", "?".to_string()),
+        ("Result", result.to_string()),
+    ];
+    if start_fen != STARTING_FEN {
+        tags.push(("SetUp", "1".to_string()));
+        tags.push(("FEN", start_fen));
+    }
+
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        pgn.push_str(&format!(
+            "[{} \"{}\"]\n",
+            name,
+            value.replace('\\', "\\\\").replace('"', "\\\"")
+        ));
+    }
+    pgn.push('\n');
+
+    let mut tokens = Vec::new();
+    let mut board = history.starting_position.clone();
+    for (i, &mv) in history.moves.iter().enumerate() {
+        if board.side_to_move == PieceColor::White {
+            tokens.push(format!("{}.", board.fullmove_number));
+        } else if i == 0 {
+            tokens.push(format!("{}...", board.fullmove_number));
+        }
+        tokens.push(board.move_to_san(mv));
+        board.make_move(mv);
+    }
+    tokens.push(result.to_string());
+
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            pgn.push(' ');
+            line_length += 1;
+        }
+        line_length += token.len();
+        pgn.push_str(&token);
+    }
+    pgn.push('\n');
+
+    pgn
+}
+
+/// Parses every game of a PGN database. Comments, NAGs, escaped lines and
+/// variations are skipped, only the main line is replayed.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = Vec::new();
+
+    let mut tags = Vec::new();
+    let mut sans = Vec::new();
+
+    for token in tokenize(text)? {
+        match token {
+            Token::Tag(name, value) => {
+                if !sans.is_empty() {
+                    games.push(build_game(std::mem::take(&mut tags), &sans, "*")?);
+                    sans.clear();
+                }
+                tags.push((name, value));
+            }
+            Token::Move(san) => sans.push(san),
+            Token::Result(result) => {
+                games.push(build_game(std::mem::take(&mut tags), &sans, &result)?);
+                sans.clear();
+            }
+        }
+    }
+
+    if !tags.is_empty() || !sans.is_empty() {
+        games.push(build_game(tags, &sans, "*")?);
+    }
+
+    Ok(games)
+}
+
+fn build_game(
+    tags: Vec<(String, String)>,
+    sans: &[String],
+    result: &str,
+) -> Result<PgnGame, PgnError> {
+    let starting_position = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Board::from_fen(fen).map_err(PgnError::InvalidFen)?,
+        None => Board::default(),
+    };
+
+    let mut board = starting_position.clone();
+    let mut moves = Vec::new();
+    for (i, san) in sans.iter().enumerate() {
+        let ply = match starting_position.side_to_move {
+            PieceColor::White => i,
+            PieceColor::Black => i + 1,
+        };
+        let mv = board
+            .parse_san(san)
+            .map_err(|error| PgnError::InvalidMove { ply, error })?;
+        board.make_move(mv);
+        moves.push(mv);
+    }
+
+    Ok(PgnGame {
+        tags,
+        starting_position,
+        moves,
+        result: result.to_string(),
+    })
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut variation_depth = 0;
+    let mut at_line_start = true;
+
+    while let Some(c) = chars.next() {
+        let was_line_start = at_line_start;
+        at_line_start = c == '\n';
+
+        match c {
+            c if c.is_whitespace() => {}
+            '%' if was_line_start => skip_line(&mut chars),
+            ';' => skip_line(&mut chars),
+            '{' => {
+                if !chars.by_ref().any(|c| c == '}') {
+                    return Err(PgnError::UnterminatedComment);
+                }
+            }
+            '(' => variation_depth += 1,
+            ')' => {
+                if variation_depth == 0 {
+                    return Err(PgnError::UnbalancedVariation);
+                }
+                variation_depth -= 1;
+            }
+            '$' => while chars.next_if(|c| c.is_ascii_digit()).is_some() {},
+            '[' => {
+                let (name, value) = read_tag(&mut chars)?;
+                if variation_depth == 0 {
+                    tokens.push(Token::Tag(name, value));
+                }
+            }
+            _ => {
+                let mut symbol = c.to_string();
+                while let Some(c) =
+                    chars.next_if(|c| !c.is_whitespace() && !"{}()[];$".contains(*c))
+                {
+                    symbol.push(c);
+                }
+
+                if variation_depth > 0 {
+                    continue;
+                }
+
+                if RESULT_TOKENS.contains(&symbol.as_str()) {
+                    tokens.push(Token::Result(symbol));
+                    continue;
+                }
+
+                let digits = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+                let san = if digits.len() < symbol.len() && digits.starts_with('.') {
+                    digits.trim_start_matches('.')
+                } else {
+                    symbol.as_str()
+                };
+
+                if !san.is_empty() {
+                    tokens.push(Token::Move(san.to_string()));
+                }
+            }
+        }
+    }
+
+    if variation_depth > 0 {
+        return Err(PgnError::UnbalancedVariation);
+    }
+
+    Ok(tokens)
+}
+
+fn skip_line(chars: &mut Peekable<Chars>) {
+    for c in chars.by_ref() {
+        if c == '\n' {
+            break;
+        }
+    }
+}
+
+fn read_tag(chars: &mut Peekable<Chars>) -> Result<(String, String), PgnError> {
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|c| *c != '"' && *c != ']') {
+        name.push(c);
+    }
+
+    let mut value = String::new();
+    if chars.next_if_eq(&'"').is_some() {
+        loop {
+            match chars.next() {
+                Some('\\') => value.extend(chars.next()),
+                Some('"') => break,
+                Some(c) => value.push(c),
+                None => return Err(PgnError::UnterminatedTag),
+            }
+        }
+    }
+
+    if !chars.by_ref().any(|c| c == ']') {
+        return Err(PgnError::UnterminatedTag);
+    }
+
+    Ok((name.trim().to_string(), value))
+}
+
+/// Current UTC date in the `YYYY.MM.DD` format of the `Date` tag.
+fn today() -> String {
+    let Ok(elapsed) = SystemTime::now().duration_since(UNIX_EPOCH) else {
+        return "????.??.??".to_string();
+    };
+
+    // Days since the epoch to a proleptic Gregorian date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (elapsed.as_secs() / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+fn save_pgn(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    history: Res<MoveHistory>,
+    state: Res<State<GameState>>,
+) {
+    let control = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !control || !keyboard_input.just_pressed(KeyCode::KeyS) {
+        return;
+    }
+
+    let result = match state.get() {
+        GameState::GameOver(result) => Some(*result),
+        _ => None,
+    };
+
+    match fs::write(PGN_EXPORT_PATH, export_pgn(&history, result)) {
+        Ok(()) => info!("Game saved to {}", PGN_EXPORT_PATH),
+        Err(error) => error!("Could not save the game to {}: {}", PGN_EXPORT_PATH, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        san::SanError,
+        state::{DrawReason, WinReason},
+    };
+
+    /// Moves of `game` written back in SAN.
+    fn san(game: &PgnGame) -> Vec<String> {
+        let mut board = game.starting_position.clone();
+        game.moves
+            .iter()
+            .map(|&mv| {
+                let san = board.move_to_san(mv);
+                board.make_move(mv);
+                san
+            })
+            .collect()
+    }
+
+    #[test]
+    fn skips_comments_nags_and_escaped_lines() {
+        let games = parse_pgn(
+            "% exported by some tool\n\
+             [Event \"Test\"]\n\
+             [White \"A \\\"quoted\\\" name\"]\n\
+             \n\
+             1. e4 {best by test} e5 $1 2. Nf3 ; rest of line ignored Nc6\n\
+             Nc6 3. Bb5!? a6 1-0\n",
+        )
+        .unwrap();
+
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(
+            game.tags,
+            vec![
+                ("Event".to_string(), "Test".to_string()),
+                ("White".to_string(), "A \"quoted\" name".to_string()),
+            ]
+        );
+        assert_eq!(san(game), ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+        assert_eq!(game.result, "1-0");
+    }
+
+    #[test]
+    fn keeps_only_the_main_line_of_nested_variations() {
+        let games =
+            parse_pgn("1. e4 (1. d4 d5 (1... Nf6 2. c4) 2. c4) 1... c5 (1... e5 2. Nf3) 2. Nf3 *")
+                .unwrap();
+
+        assert_eq!(san(&games[0]), ["e4", "c5", "Nf3"]);
+        assert_eq!(games[0].result, "*");
+    }
+
+    #[test]
+    fn reads_several_games_and_fen_tags() {
+        let games = parse_pgn(
+            "[Event \"First\"]\n\n1. d4 d5 1/2-1/2\n\n\
+             [Event \"Second\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/4K2R w K - 0 1\"]\n\n\
+             1. O-O Kd7 0-1\n",
+        )
+        .unwrap();
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(san(&games[0]), ["d4", "d5"]);
+        assert_eq!(games[0].result, "1/2-1/2");
+        assert_eq!(
+            games[1].starting_position.to_fen(),
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1"
+        );
+        assert_eq!(san(&games[1]), ["O-O", "Kd7"]);
+        assert_eq!(games[1].result, "0-1");
+    }
+
+    #[test]
+    fn reports_malformed_input() {
+        assert_eq!(
+            parse_pgn("1. e4 {unclosed").unwrap_err(),
+            PgnError::UnterminatedComment
+        );
+        assert_eq!(
+            parse_pgn("[Event \"Test\"\n1. e4").unwrap_err(),
+            PgnError::UnterminatedTag
+        );
+        assert_eq!(
+            parse_pgn("1. e4 (1. d4 *").unwrap_err(),
+            PgnError::UnbalancedVariation
+        );
+        assert_eq!(
+            parse_pgn("1. e4 e5 ) *").unwrap_err(),
+            PgnError::UnbalancedVariation
+        );
+        assert_eq!(
+            parse_pgn("1. e4 e5 2. Ke3 *").unwrap_err(),
+            PgnError::InvalidMove {
+                ply: 2,
+                error: SanError::Illegal("Ke3".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn exported_games_parse_back() {
+        let mut history = MoveHistory::new(Board::default());
+        let mut board = Board::default();
+        for san in [
+            "e4", "d5", "exd5", "Qxd5", "Nc3", "Qa5", "d4", "c6", "Nf3", "Bf5", "Bc4", "e6", "O-O",
+            "Nd7", "Re1", "Ngf6", "d5", "cxd5", "Bxd5", "O-O-O",
+        ] {
+            let mv = board.parse_san(san).unwrap();
+            board.make_move(mv);
+            history.moves.push(mv);
+        }
+
+        let pgn = export_pgn(&history, Some(GameResult::Draw(DrawReason::Stalemate)));
+        let games = parse_pgn(&pgn).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].moves, history.moves);
+        assert_eq!(games[0].result, "1/2-1/2");
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+
+        let history = MoveHistory::new(Board::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 0 1").unwrap());
+        let pgn = export_pgn(&history, Some(GameResult::WhiteWins(WinReason::Checkmate)));
+        let games = parse_pgn(&pgn).unwrap();
+        assert!(pgn.contains("[SetUp \"1\"]"));
+        assert_eq!(
+            games[0].starting_position.to_fen(),
+            "4k3/8/8/8/8/8/8/4K2R b K - 0 1"
+        );
+        assert_eq!(games[0].result, "1-0");
+    }
+}
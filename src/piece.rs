@@ -1,9 +1,14 @@
 use std::fmt;
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    board::{print_board, Board, BoardConfiguration, PieceEntity},
+    board::{Board, BoardConfiguration, PieceEntity},
+    history::MoveHistory,
+    network::NetworkConnection,
+    promotion::PendingPromotion,
+    rules::{Move, Square},
     state::GameState,
     CursorPosition, SPRITE_SHEET_W,
 };
@@ -12,19 +17,41 @@ pub struct PiecePlugin;
 
 impl Plugin for PiecePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                add_dragging,
-                handle_dragging,
-                handle_drop.after(handle_dragging),
-            )
-                .run_if(in_state(GameState::InGame)),
-        );
+        app.init_resource::<LocalPlayer>()
+            .add_event::<MoveRequest>()
+            .configure_sets(Update, (MoveSet::Input, MoveSet::Apply).chain())
+            .add_systems(
+                Update,
+                (
+                    (
+                        add_dragging.run_if(not(resource_exists::<PendingPromotion>)),
+                        handle_dragging,
+                        handle_drop.after(handle_dragging),
+                    )
+                        .in_set(MoveSet::Input),
+                    play_move_requests
+                        .in_set(MoveSet::Apply)
+                        .run_if(not(resource_exists::<NetworkConnection>)),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            );
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Move the local player asked for. Local games play it right away, network
+/// games send it to the server and wait for it to come back.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MoveRequest(pub Move);
+
+/// Systems turning player input into `MoveRequest`s run in `Input`, the ones
+/// playing moves on the board in `Apply`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MoveSet {
+    Input,
+    Apply,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum PieceType {
     King,
     Queen,
@@ -34,25 +61,83 @@ pub enum PieceType {
     Pawn,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum PieceColor {
     White,
     Black,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Piece {
     pub piece_type: PieceType,
     pub color: PieceColor,
     pub index: usize,
 }
 
+/// Colors the person in front of this window may move: both in a hot-seat
+/// game, a single one when playing over the network.
+#[derive(Resource, Clone, Copy)]
+pub struct LocalPlayer {
+    pub white: bool,
+    pub black: bool,
+}
+
 #[derive(Component)]
 pub struct Dragging {
     init_x: usize,
     init_y: usize,
 }
 
+impl LocalPlayer {
+    pub fn only(color: PieceColor) -> Self {
+        Self {
+            white: color == PieceColor::White,
+            black: color == PieceColor::Black,
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            white: false,
+            black: false,
+        }
+    }
+
+    pub fn controls(&self, color: PieceColor) -> bool {
+        match color {
+            PieceColor::White => self.white,
+            PieceColor::Black => self.black,
+        }
+    }
+}
+
+impl Default for LocalPlayer {
+    fn default() -> Self {
+        Self {
+            white: true,
+            black: true,
+        }
+    }
+}
+
+impl PieceColor {
+    pub fn opponent(&self) -> PieceColor {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+}
+
+impl fmt::Display for PieceColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PieceColor::White => write!(f, "White"),
+            PieceColor::Black => write!(f, "Black"),
+        }
+    }
+}
+
 impl fmt::Display for PieceType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let piece_char = match self {
@@ -92,9 +177,10 @@ pub fn get_piece_index(piece_type: PieceType, color: PieceColor) -> usize {
         index += SPRITE_SHEET_W;
     }
 
-    return index as usize;
+    index as usize
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn add_dragging(
     mut commands: Commands,
     board_config: Res<BoardConfiguration>,
@@ -102,6 +188,8 @@ fn add_dragging(
     piece_dragged_query: Query<(&Transform, Entity), (With<PieceEntity>, With<Dragging>)>,
     cursor_position: Res<CursorPosition>,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
+    board: Res<Board>,
+    local_player: Res<LocalPlayer>,
 ) {
     if !mouse_button_input.pressed(MouseButton::Left) {
         return;
@@ -111,6 +199,10 @@ fn add_dragging(
         return;
     }
 
+    if !local_player.controls(board.side_to_move) {
+        return;
+    }
+
     if piece_query.is_empty() {
         return;
     }
@@ -118,24 +210,29 @@ fn add_dragging(
     if let Some(cursor_position) = cursor_position.position {
         for (transform_piece, entity_piece) in piece_query.iter_mut() {
             if transform_piece.translation.x
-                > cursor_position.x - board_config.half_cell_size as f32
+                > cursor_position.x - board_config.half_cell_size
                 && transform_piece.translation.x
-                    < cursor_position.x + board_config.half_cell_size as f32
+                    < cursor_position.x + board_config.half_cell_size
                 && transform_piece.translation.y
-                    < cursor_position.y + board_config.half_cell_size as f32
+                    < cursor_position.y + board_config.half_cell_size
                 && transform_piece.translation.y
-                    > cursor_position.y - board_config.half_cell_size as f32
+                    > cursor_position.y - board_config.half_cell_size
             {
-                let board_x = (((-board_config.board_origin.x + cursor_position.x)
-                    / board_config.cell_size) as usize)
-                    .clamp(0, 7);
-                let board_y = (((-board_config.board_origin.y + cursor_position.y)
-                    / -board_config.cell_size) as usize)
-                    .clamp(0, 7);
+                let Some(square) = board_config.square_at(transform_piece.translation.truncate())
+                else {
+                    continue;
+                };
+
+                if !board
+                    .piece_at(square)
+                    .is_some_and(|piece| piece.color == board.side_to_move)
+                {
+                    continue;
+                }
 
                 commands.entity(entity_piece).insert(Dragging {
-                    init_x: board_x,
-                    init_y: board_y,
+                    init_x: square.x,
+                    init_y: square.y,
                 });
 
                 return;
@@ -159,54 +256,124 @@ fn handle_dragging(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_drop(
     mut commands: Commands,
     board_config: Res<BoardConfiguration>,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut dragging_query: Query<(&mut Transform, Entity, &Dragging), With<Dragging>>,
+    dragging_query: Query<(Entity, &Dragging)>,
     cursor_position: Res<CursorPosition>,
-    mut board: ResMut<Board>,
-    mut piece_query: Query<(&Transform, Entity), (With<PieceEntity>, Without<Dragging>)>,
+    board: Res<Board>,
+    mut transform_query: Query<&mut Transform, With<PieceEntity>>,
+    mut move_requests: EventWriter<MoveRequest>,
 ) {
     if !mouse_button_input.just_released(MouseButton::Left) {
         return;
     }
 
-    for (mut transform_dragging, entity_piece, dragging) in dragging_query.iter_mut() {
-        let board_x = (((-board_config.board_origin.x + cursor_position.position.unwrap().x)
-            / board_config.cell_size) as usize)
-            .clamp(0, 7);
-        let board_y = (((-board_config.board_origin.y + cursor_position.position.unwrap().y)
-            / -board_config.cell_size) as usize)
-            .clamp(0, 7);
+    for (entity_piece, dragging) in dragging_query.iter() {
+        commands.entity(entity_piece).remove::<Dragging>();
 
-        transform_dragging.translation = Vec3::new(board_config.board_origin.x + board_x as f32 * board_config.cell_size + board_config.half_cell_size, board_config.board_origin.y - board_y as f32 * board_config.cell_size - board_config.half_cell_size, 1.0);
+        let from = Square::new(dragging.init_x, dragging.init_y);
 
+        if let Ok(mut transform_dragging) = transform_query.get_mut(entity_piece) {
+            let origin = board_config.square_center(from);
+            transform_dragging.translation = Vec3::new(origin.x, origin.y, 1.0);
+        }
 
-        board.pieces[board_y][board_x].take();
+        let Some(to) = cursor_position
+            .position
+            .and_then(|position| board_config.square_at(position))
+        else {
+            continue;
+        };
 
-        if let Some(cursor_position) = cursor_position.position {
-            for (transform_piece, entity_piece) in piece_query.iter_mut() {
-                if transform_piece.translation.x
-                    > cursor_position.x - board_config.half_cell_size as f32
-                    && transform_piece.translation.x
-                        < cursor_position.x + board_config.half_cell_size as f32
-                    && transform_piece.translation.y
-                        < cursor_position.y + board_config.half_cell_size as f32
-                    && transform_piece.translation.y
-                        > cursor_position.y - board_config.half_cell_size as f32
-                {
-                    commands.entity(entity_piece).despawn();
-                }
-            }
+        if board.is_legal_move(Move::with_promotion(from, to, PieceType::Queen)) {
+            commands.insert_resource(PendingPromotion {
+                mv: Move::new(from, to),
+            });
+            continue;
         }
 
-        if let Some(piece) = board.pieces[dragging.init_y][dragging.init_x].take() {
-            board.pieces[board_y][board_x] = Some(piece);
+        if board.is_legal_move(Move::new(from, to)) {
+            move_requests.send(MoveRequest(Move::new(from, to)));
         }
+    }
+}
 
-        commands.entity(entity_piece).remove::<Dragging>();
+fn play_move_requests(
+    mut commands: Commands,
+    mut move_requests: EventReader<MoveRequest>,
+    board_config: Res<BoardConfiguration>,
+    mut board: ResMut<Board>,
+    mut piece_query: Query<(Entity, &mut Transform, &mut TextureAtlas), With<PieceEntity>>,
+    mut history: ResMut<MoveHistory>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for &MoveRequest(mv) in move_requests.read() {
+        if !board.is_legal_move(mv) {
+            continue;
+        }
+
+        play_move(
+            &mut commands,
+            &board_config,
+            &mut board,
+            mv,
+            &mut piece_query,
+            &mut history,
+            &mut next_state,
+        );
+    }
+}
+
+/// Plays the legal move `mv` on `board`, records it in `history` and mirrors
+/// it on the piece entities: captured pieces are despawned, the moving piece
+/// (and the rook when castling) is placed on its destination and promoted
+/// pawns get their new sprite.
+pub fn play_move(
+    commands: &mut Commands,
+    board_config: &BoardConfiguration,
+    board: &mut Board,
+    mv: Move,
+    piece_query: &mut Query<(Entity, &mut Transform, &mut TextureAtlas), With<PieceEntity>>,
+    history: &mut MoveHistory,
+    next_state: &mut NextState<GameState>,
+) {
+    let Some(color) = board.piece_at(mv.from).map(|piece| piece.color) else {
+        return;
+    };
+
+    let captured_square = board.en_passant_capture_square(mv).unwrap_or(mv.to);
+    let rook_move = board.castling_rook_move(mv);
+
+    for (entity, mut transform, mut texture_atlas) in piece_query.iter_mut() {
+        let square = board_config.square_at(transform.translation.truncate());
+
+        if square == Some(mv.from) {
+            let destination = board_config.square_center(mv.to);
+            transform.translation = Vec3::new(destination.x, destination.y, 1.0);
+
+            if let Some(piece_type) = mv.promotion {
+                texture_atlas.index = get_piece_index(piece_type, color);
+            }
+        } else if square == Some(captured_square) {
+            commands.entity(entity).despawn();
+        } else if let Some(rook_move) = rook_move.filter(|rook| square == Some(rook.from)) {
+            let destination = board_config.square_center(rook_move.to);
+            transform.translation = Vec3::new(destination.x, destination.y, 1.0);
+        }
+    }
+
+    board.make_move(mv);
+    history.moves.push(mv);
 
-        print_board(&board);
+    if let Some(result) = board.game_result() {
+        info!("{}", result);
+        next_state.set(GameState::GameOver(result));
+    } else if board.is_in_check(board.side_to_move) {
+        info!("{} is in check", board.side_to_move);
+    } else {
+        info!("{} to move", board.side_to_move);
     }
 }
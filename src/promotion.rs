@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{Board, BoardConfiguration},
+    piece::{get_piece_index, MoveRequest, MoveSet, PieceColor},
+    rules::{pawn_direction, Move, Square, PROMOTION_PIECES},
+    state::GameState,
+    CursorPosition, GlobalTextureAtlas, SPRITE_W,
+};
+
+pub struct PromotionPlugin;
+
+/// A pawn move onto the last rank waiting for the player to pick the
+/// promotion piece.
+#[derive(Resource)]
+pub struct PendingPromotion {
+    pub mv: Move,
+}
+
+#[derive(Component)]
+struct PromotionChoice;
+
+impl Plugin for PromotionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                spawn_promotion_picker.run_if(
+                    resource_exists_and_changed::<PendingPromotion>
+                        .or_else(resource_exists_and_changed::<BoardConfiguration>),
+                ),
+                handle_promotion_choice,
+            )
+                .chain()
+                .in_set(MoveSet::Input)
+                .run_if(in_state(GameState::InGame).and_then(resource_exists::<PendingPromotion>)),
+        );
+    }
+}
+
+/// Square of the `i`-th entry of `PROMOTION_PIECES` in the picker, laid out
+/// from the promotion square towards the center of the board.
+fn choice_square(mv: Move, color: PieceColor, i: usize) -> Option<Square> {
+    mv.to.offset(0, -pawn_direction(color) * i as i32)
+}
+
+fn spawn_promotion_picker(
+    mut commands: Commands,
+    handle: Res<GlobalTextureAtlas>,
+    board: Res<Board>,
+    board_config: Res<BoardConfiguration>,
+    pending: Res<PendingPromotion>,
+    choice_query: Query<Entity, With<PromotionChoice>>,
+) {
+    for entity in choice_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let Some(pawn) = board.piece_at(pending.mv.from) else {
+        return;
+    };
+
+    for (i, &piece_type) in PROMOTION_PIECES.iter().enumerate() {
+        let Some(square) = choice_square(pending.mv, pawn.color, i) else {
+            continue;
+        };
+        let center = board_config.square_center(square);
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(0.9, 0.9, 0.9),
+                    custom_size: Some(Vec2::splat(board_config.cell_size)),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(center.x, center.y, 3.0)),
+                ..default()
+            },
+            PromotionChoice,
+        ));
+
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform::from_translation(Vec3::new(center.x, center.y, 4.0))
+                    .with_scale(Vec3::splat(board_config.cell_size / SPRITE_W as f32)),
+                texture: handle.image.clone().unwrap(),
+                ..default()
+            },
+            TextureAtlas {
+                layout: handle.layout.clone().unwrap(),
+                index: get_piece_index(piece_type, pawn.color),
+            },
+            PromotionChoice,
+        ));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_promotion_choice(
+    mut commands: Commands,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    cursor_position: Res<CursorPosition>,
+    board_config: Res<BoardConfiguration>,
+    board: Res<Board>,
+    pending: Res<PendingPromotion>,
+    choice_query: Query<Entity, With<PromotionChoice>>,
+    mut move_requests: EventWriter<MoveRequest>,
+) {
+    if !mouse_button_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    for entity in choice_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<PendingPromotion>();
+
+    let (Some(pawn), Some(clicked)) = (
+        board.piece_at(pending.mv.from),
+        cursor_position
+            .position
+            .and_then(|position| board_config.square_at(position)),
+    ) else {
+        return;
+    };
+
+    let choice = PROMOTION_PIECES
+        .iter()
+        .enumerate()
+        .find(|&(i, _)| choice_square(pending.mv, pawn.color, i) == Some(clicked));
+
+    if let Some((_, &piece_type)) = choice {
+        move_requests.send(MoveRequest(Move::with_promotion(
+            pending.mv.from,
+            pending.mv.to,
+            piece_type,
+        )));
+    }
+}
@@ -0,0 +1,425 @@
+use std::{error::Error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::TimeControl, history::MoveHistory, piece::PieceColor, rules::Move, state::GameResult,
+};
+
+/// Version of the wire protocol, to be bumped whenever `Message` changes in
+/// a way an older peer would not understand.
+pub const PROTOCOL_VERSION: u32 = 8;
+
+/// Messages exchanged between the server and a player, one JSON object per
+/// line. Both ends open with `Hello`, whose shape must never change so that
+/// peers of different versions can still find out they do not match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// First message sent by either end, announcing its protocol version.
+    Hello {
+        version: u32,
+    },
+    /// Open seeks and games being played, sent by the server to the players
+    /// in the lobby whenever they change.
+    Lobby {
+        seeks: Vec<Seek>,
+        games: Vec<LiveGame>,
+    },
+    /// The sender looks for an opponent, replacing its previous seek.
+    CreateSeek {
+        options: SeekOptions,
+    },
+    CancelSeek,
+    /// The sender takes up seek `id` and starts playing its creator.
+    AcceptSeek {
+        id: u64,
+    },
+    /// The sender wants to follow game `id` as a spectator.
+    Watch {
+        id: u64,
+    },
+    StopWatching,
+    /// Sent by the server to a new spectator of game `id`: time control,
+    /// whether the game is rated, starting position and moves so far.
+    Spectate {
+        id: u64,
+        time_control: TimeControl,
+        rated: bool,
+        history: Box<MoveHistory>,
+    },
+    /// Sent by the server when a game starts or is resumed: color of the
+    /// receiving player, time control, whether the game is rated, session
+    /// token to resume the game with, starting position and moves so far.
+    Start {
+        color: PieceColor,
+        time_control: TimeControl,
+        rated: bool,
+        token: String,
+        history: Box<MoveHistory>,
+    },
+    /// The sender lost its connection during the game holding `token` and
+    /// wants to take its seat back.
+    Resume {
+        token: String,
+    },
+    /// The opponent lost its connection and forfeits the game unless it
+    /// comes back within `grace_secs`.
+    OpponentDisconnected {
+        grace_secs: u64,
+    },
+    OpponentReconnected,
+    /// Move asked for by a player, or accepted by the server when sent to
+    /// the players.
+    Move {
+        mv: Move,
+    },
+    /// The sender gives up the game.
+    Resign,
+    /// The sender offers a draw, or accepts the one its opponent offered.
+    DrawOffer,
+    /// The sender turns down the draw its opponent offered.
+    DeclineDraw,
+    /// The sender, to move, claims a draw by threefold repetition or the
+    /// fifty-move rule.
+    ClaimDraw,
+    /// The sender asks to take back its last move, or grants the takeback
+    /// its opponent asked for.
+    Takeback,
+    /// The sender turns down the takeback its opponent asked for.
+    DeclineTakeback,
+    /// Sent by the server once a takeback is granted: the last `plies` moves
+    /// of the game are undone.
+    TakenBack {
+        plies: usize,
+    },
+    /// Time left on each clock in milliseconds as counted by the server,
+    /// sent whenever a clock is pressed, and the side whose clock runs.
+    ClockSync {
+        white_ms: u64,
+        black_ms: u64,
+        running: Option<PieceColor>,
+    },
+    /// Sent by the server to measure the lag of a player, who answers with a
+    /// `Pong` of the same `id` right away.
+    Ping {
+        id: u64,
+    },
+    Pong {
+        id: u64,
+    },
+    /// Chat line typed by the sender in `channel` of the game it plays or
+    /// watches.
+    SendChat {
+        channel: ChatChannel,
+        text: String,
+    },
+    /// Chat line relayed by the server to everyone reading `channel`, `mine`
+    /// telling whether the receiving player wrote it.
+    Chat {
+        channel: ChatChannel,
+        from: String,
+        text: String,
+        mine: bool,
+    },
+    /// Sent by the server once a game is over, whatever ended it.
+    GameEnd {
+        result: GameResult,
+    },
+    /// Sent by the server when a request was refused or the game was
+    /// interrupted.
+    Error {
+        message: String,
+    },
+}
+
+/// Color the player creating a seek wants to play.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ColorPreference {
+    #[default]
+    Random,
+    White,
+    Black,
+}
+
+/// Kind of game a player is looking for.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SeekOptions {
+    pub time_control: TimeControl,
+    pub color: ColorPreference,
+    pub rated: bool,
+}
+
+/// Open seek as listed by the server, `mine` telling whether the receiving
+/// player created it.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Seek {
+    pub id: u64,
+    pub options: SeekOptions,
+    pub mine: bool,
+}
+
+/// Game being played on the server, which lobby players may watch.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LiveGame {
+    pub id: u64,
+    pub time_control: TimeControl,
+    pub rated: bool,
+    pub spectators: usize,
+}
+
+/// Chat room of a network game. Players talk in `Game`, which spectators
+/// can read, while spectators also have a room of their own.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ChatChannel {
+    #[default]
+    Game,
+    Spectators,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    Malformed(String),
+    VersionMismatch { ours: u32, theirs: u32 },
+    IllegalMove(Move),
+}
+
+impl Message {
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("messages always serialize")
+    }
+
+    /// Parses a line received from the other end. Moves of a `Start` or
+    /// `Spectate` message are checked for legality since they are replayed
+    /// as is.
+    pub fn decode(line: &str) -> Result<Message, ProtocolError> {
+        let message: Message = serde_json::from_str(line.trim())
+            .map_err(|error| ProtocolError::Malformed(error.to_string()))?;
+
+        if let Message::Start { history, .. } | Message::Spectate { history, .. } = &message {
+            let mut board = history.starting_position.clone();
+            for &mv in history.moves.iter() {
+                if !board.is_legal_move(mv) {
+                    return Err(ProtocolError::IllegalMove(mv));
+                }
+                board.make_move(mv);
+            }
+        }
+
+        Ok(message)
+    }
+
+    pub fn error(message: impl Into<String>) -> Message {
+        Message::Error {
+            message: message.into(),
+        }
+    }
+}
+
+/// Checks the version announced by the other end's `Hello`.
+pub fn check_version(theirs: u32) -> Result<(), ProtocolError> {
+    if theirs == PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(ProtocolError::VersionMismatch {
+            ours: PROTOCOL_VERSION,
+            theirs,
+        })
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Malformed(error) => write!(f, "malformed message: {}", error),
+            ProtocolError::VersionMismatch { ours, theirs } => write!(
+                f,
+                "protocol version mismatch (expected {}, got {})",
+                ours, theirs
+            ),
+            ProtocolError::IllegalMove(mv) => write!(f, "illegal move {}", mv),
+        }
+    }
+}
+
+impl Error for ProtocolError {}
+
+impl fmt::Display for ColorPreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorPreference::Random => write!(f, "Random"),
+            ColorPreference::White => write!(f, "White"),
+            ColorPreference::Black => write!(f, "Black"),
+        }
+    }
+}
+
+impl fmt::Display for SeekOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  {}  {}",
+            self.time_control,
+            self.color,
+            if self.rated { "Rated" } else { "Casual" }
+        )
+    }
+}
+
+impl fmt::Display for ChatChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatChannel::Game => write!(f, "Game"),
+            ChatChannel::Spectators => write!(f, "Spectators"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::Board,
+        state::{DrawReason, WinReason},
+    };
+
+    fn round_trip(message: Message) {
+        let line = message.encode();
+        assert!(!line.contains('\n'), "{} spans several lines", line);
+        let decoded = Message::decode(&line).unwrap();
+        assert_eq!(decoded.encode(), line);
+    }
+
+    #[test]
+    fn messages_survive_encoding() {
+        let time_control: TimeControl = "40/90, 30+30".parse().unwrap();
+        let options = SeekOptions {
+            time_control: time_control.clone(),
+            color: ColorPreference::Black,
+            rated: true,
+        };
+
+        let mut history = MoveHistory::new(Board::default());
+        let mut board = Board::default();
+        for uci in ["e2e4", "d7d5", "e4d5", "g8f6"] {
+            let mv = board
+                .legal_moves(board.side_to_move)
+                .into_iter()
+                .find(|&mv| mv == Move::from_uci(uci).unwrap())
+                .unwrap();
+            board.make_move(mv);
+            history.moves.push(mv);
+        }
+
+        round_trip(Message::Hello {
+            version: PROTOCOL_VERSION,
+        });
+        round_trip(Message::CreateSeek {
+            options: options.clone(),
+        });
+        round_trip(Message::Lobby {
+            seeks: vec![Seek {
+                id: 3,
+                options,
+                mine: false,
+            }],
+            games: vec![LiveGame {
+                id: 7,
+                time_control: time_control.clone(),
+                rated: false,
+                spectators: 2,
+            }],
+        });
+        round_trip(Message::Start {
+            color: PieceColor::Black,
+            time_control,
+            rated: true,
+            token: "0123456789abcdef".to_string(),
+            history: Box::new(history),
+        });
+        round_trip(Message::Resume {
+            token: "0123456789abcdef".to_string(),
+        });
+        round_trip(Message::Move {
+            mv: Move::from_uci("a7a8q").unwrap(),
+        });
+        round_trip(Message::ClockSync {
+            white_ms: 59_950,
+            black_ms: 61_200,
+            running: Some(PieceColor::White),
+        });
+        round_trip(Message::Chat {
+            channel: ChatChannel::Spectators,
+            from: "Spectator 4".to_string(),
+            text: "a \"quoted\"\nline".to_string(),
+            mine: true,
+        });
+        round_trip(Message::GameEnd {
+            result: GameResult::WhiteWins(WinReason::Timeout),
+        });
+        round_trip(Message::GameEnd {
+            result: GameResult::Draw(DrawReason::Agreement),
+        });
+        round_trip(Message::DrawOffer);
+        round_trip(Message::error("This seek is no longer open"));
+    }
+
+    #[test]
+    fn version_mismatch_is_reported() {
+        let newer = Message::decode(r#"{"type":"hello","version":99,"extra":[1,2]}"#);
+        let Ok(Message::Hello { version }) = newer else {
+            panic!("a hello from a newer peer must still decode: {:?}", newer);
+        };
+
+        let error = check_version(version).unwrap_err();
+        assert_eq!(
+            error,
+            ProtocolError::VersionMismatch {
+                ours: PROTOCOL_VERSION,
+                theirs: 99
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "protocol version mismatch (expected {}, got 99)",
+                PROTOCOL_VERSION
+            )
+        );
+        assert!(check_version(PROTOCOL_VERSION).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        for line in [
+            "",
+            "not json",
+            r#"{"type":"teleport"}"#,
+            r#"{"type":"move"}"#,
+        ] {
+            assert!(matches!(
+                Message::decode(line),
+                Err(ProtocolError::Malformed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_illegal_moves_in_a_game_history() {
+        let mut history = MoveHistory::new(Board::default());
+        history.moves.push(Move::from_uci("e2e5").unwrap());
+        let line = Message::Start {
+            color: PieceColor::White,
+            time_control: TimeControl::new(300, 3),
+            rated: false,
+            token: String::new(),
+            history: Box::new(history),
+        }
+        .encode();
+
+        assert_eq!(
+            Message::decode(&line).unwrap_err(),
+            ProtocolError::IllegalMove(Move::from_uci("e2e5").unwrap())
+        );
+    }
+}
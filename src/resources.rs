@@ -1,26 +1,34 @@
 use bevy::{prelude::*, window::PrimaryWindow};
 
 use crate::{
-    board::{Board, BoardConfiguration},
+    ai::ComputerOpponent,
+    board::BoardConfiguration,
+    history::MoveHistory,
+    options::LaunchOptions,
     state::GameState,
     BG_COLOR, SPRITE_H, SPRITE_SHEET_H, SPRITE_SHEET_PATH, SPRITE_SHEET_W, SPRITE_W,
 };
 
 pub struct ResourcesPlugin;
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 struct LoadCompletion {
     setup_background_color: bool,
     load_assets: bool,
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct GlobalTextureAtlas {
     pub layout: Option<Handle<TextureAtlasLayout>>,
     pub image: Option<Handle<Image>>,
 }
 
+/// Game set up the next time `GameInitResources` is entered, instead of the
+/// one given on the command line.
 #[derive(Resource)]
+pub struct NextGame(pub MoveHistory);
+
+#[derive(Resource, Default)]
 pub struct CursorPosition {
     pub position: Option<Vec2>,
 }
@@ -69,16 +77,38 @@ fn load_assets(
 }
 
 fn check_load_completion(
+    mut commands: Commands,
     load_completion: Res<LoadCompletion>,
+    options: Res<LaunchOptions>,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
     if load_completion.setup_background_color && load_completion.load_assets {
-        next_state.set(GameState::GameInitResources);
+        // Network games are picked in the lobby and local ones in the main
+        // menu, unless the computer was asked for on the command line.
+        if options.computer {
+            commands.insert_resource(ComputerOpponent::default());
+            next_state.set(GameState::GameInitResources);
+        } else {
+            next_state.set(GameState::MainMenu);
+        }
     }
 }
 
-fn setup_board_resource(mut commands: Commands, mut next_state: ResMut<NextState<GameState>>) {
-    commands.insert_resource(Board::default());
+fn setup_board_resource(
+    mut commands: Commands,
+    options: Res<LaunchOptions>,
+    next_game: Option<Res<NextGame>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let history = match next_game {
+        Some(next_game) => {
+            commands.remove_resource::<NextGame>();
+            next_game.0.clone()
+        }
+        None => options.game.clone().unwrap_or_default(),
+    };
+    commands.insert_resource(history.current_position());
+    commands.insert_resource(history);
     commands.insert_resource(BoardConfiguration::default());
 
     next_state.set(GameState::GameInitEntities);
@@ -100,27 +130,3 @@ fn update_cursor_position(
         .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
         .map(|ray| ray.origin.truncate());
 }
-
-impl Default for LoadCompletion {
-    fn default() -> Self {
-        Self {
-            setup_background_color: false,
-            load_assets: false,
-        }
-    }
-}
-
-impl Default for GlobalTextureAtlas {
-    fn default() -> Self {
-        Self {
-            layout: None,
-            image: None,
-        }
-    }
-}
-
-impl Default for CursorPosition {
-    fn default() -> Self {
-        Self { position: None }
-    }
-}
@@ -0,0 +1,1049 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::BitOr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bitboard::{
+        bishop_attacks, king_attacks, knight_attacks, pawn_attacks, queen_attacks, rook_attacks,
+        square_bit, squares, Bitboard,
+    },
+    board::{Board, CastlingRights},
+    history::MoveHistory,
+    piece::{get_piece_index, Piece, PieceColor, PieceType},
+    state::{DrawReason, GameResult, WinReason},
+    zobrist::piece_key,
+};
+
+/// Halfmove clock from which a draw may be claimed, then is declared, under
+/// the fifty-move and seventy-five-move rules.
+const FIFTY_MOVE_PLIES: u32 = 100;
+const SEVENTY_FIVE_MOVE_PLIES: u32 = 150;
+
+/// A square on the board, `x` being the file (0 = a) and `y` the row of
+/// `Board::pieces` (0 = 8th rank). Serialized in algebraic notation.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Square {
+    pub x: usize,
+    pub y: usize,
+}
+
+/// Pieces a pawn may turn into on the last rank, most common first.
+pub const PROMOTION_PIECES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Knight,
+    PieceType::Rook,
+    PieceType::Bishop,
+];
+
+/// Serialized in UCI coordinate notation, which leaves out the flags.
+/// Moves typed by a player or received from the network come without them,
+/// so they are ignored when comparing moves too.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<PieceType>,
+    pub flags: MoveFlags,
+}
+
+/// What a move does besides carrying a piece from one square to another,
+/// filled in by move generation and by `Board::make_move`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct MoveFlags(u8);
+
+/// Everything `Board::make_move` changed besides the pieces, for
+/// `Board::unmake_move` to put back.
+#[derive(Debug, Clone)]
+pub struct Undo {
+    /// The move played, with its flags.
+    mv: Move,
+    moved: Option<Piece>,
+    captured: Option<Piece>,
+    castling_rights: CastlingRights,
+    en_passant: Option<Square>,
+    side_to_move: PieceColor,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    hash: u64,
+    /// Length of `Board::position_history` before the move, or the whole of
+    /// it when the move cleared it.
+    position_history_len: usize,
+    cleared_position_history: Option<Vec<u64>>,
+}
+
+impl Square {
+    pub fn new(x: usize, y: usize) -> Self {
+        Self { x, y }
+    }
+
+    pub fn offset(&self, dx: i32, dy: i32) -> Option<Square> {
+        let x = self.x as i32 + dx;
+        let y = self.y as i32 + dy;
+
+        if (0..8).contains(&x) && (0..8).contains(&y) {
+            Some(Square::new(x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+
+    /// Parses a square in algebraic notation such as `e4`.
+    pub fn from_algebraic(text: &str) -> Option<Square> {
+        let mut chars = text.chars();
+        let (file, rank) = (chars.next()?, chars.next()?);
+
+        if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+            return None;
+        }
+
+        Some(Square::new(
+            file as usize - 'a' as usize,
+            '8' as usize - rank as usize,
+        ))
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            (b'a' + self.x as u8) as char,
+            (b'8' - self.y as u8) as char
+        )
+    }
+}
+
+impl MoveFlags {
+    pub const QUIET: MoveFlags = MoveFlags(0);
+    pub const CAPTURE: MoveFlags = MoveFlags(1);
+    pub const DOUBLE_PAWN_PUSH: MoveFlags = MoveFlags(1 << 1);
+    pub const EN_PASSANT: MoveFlags = MoveFlags(1 << 2);
+    pub const CASTLING: MoveFlags = MoveFlags(1 << 3);
+
+    pub fn contains(self, other: MoveFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for MoveFlags {
+    type Output = MoveFlags;
+
+    fn bitor(self, other: MoveFlags) -> MoveFlags {
+        MoveFlags(self.0 | other.0)
+    }
+}
+
+impl Move {
+    pub fn new(from: Square, to: Square) -> Self {
+        Self {
+            from,
+            to,
+            promotion: None,
+            flags: MoveFlags::QUIET,
+        }
+    }
+
+    pub fn with_promotion(from: Square, to: Square, promotion: PieceType) -> Self {
+        Self {
+            from,
+            to,
+            promotion: Some(promotion),
+            flags: MoveFlags::QUIET,
+        }
+    }
+
+    pub fn with_flags(self, flags: MoveFlags) -> Self {
+        Self { flags, ..self }
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.flags.contains(MoveFlags::CAPTURE)
+    }
+
+    /// Parses a move in the coordinate notation used by UCI, such as `e2e4`
+    /// or `e7e8q`.
+    pub fn from_uci(text: &str) -> Option<Move> {
+        if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+            return None;
+        }
+
+        let from = Square::from_algebraic(&text[0..2])?;
+        let to = Square::from_algebraic(&text[2..4])?;
+        let promotion = match text.get(4..) {
+            Some("q") => Some(PieceType::Queen),
+            Some("r") => Some(PieceType::Rook),
+            Some("b") => Some(PieceType::Bishop),
+            Some("n") => Some(PieceType::Knight),
+            Some("") => None,
+            _ => return None,
+        };
+
+        Some(Move {
+            from,
+            to,
+            promotion,
+            flags: MoveFlags::QUIET,
+        })
+    }
+}
+
+impl PartialEq for Move {
+    fn eq(&self, other: &Move) -> bool {
+        (self.from, self.to, self.promotion) == (other.from, other.to, other.promotion)
+    }
+}
+
+impl Eq for Move {}
+
+impl Hash for Move {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.from, self.to, self.promotion).hash(state);
+    }
+}
+
+/// Formats the move in UCI coordinate notation.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(piece_type) = self.promotion {
+            write!(f, "{}", piece_type.to_string().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Square> for String {
+    fn from(square: Square) -> String {
+        square.to_string()
+    }
+}
+
+impl TryFrom<String> for Square {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Square, String> {
+        Square::from_algebraic(&text).ok_or_else(|| format!("invalid square '{}'", text))
+    }
+}
+
+impl From<Move> for String {
+    fn from(mv: Move) -> String {
+        mv.to_string()
+    }
+}
+
+impl TryFrom<String> for Move {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Move, String> {
+        Move::from_uci(&text).ok_or_else(|| format!("invalid move '{}'", text))
+    }
+}
+
+/// Row direction in which pawns of `color` advance.
+pub fn pawn_direction(color: PieceColor) -> i32 {
+    match color {
+        PieceColor::White => -1,
+        PieceColor::Black => 1,
+    }
+}
+
+/// Row of `Board::pieces` holding the back rank of `color`.
+pub fn home_row(color: PieceColor) -> usize {
+    match color {
+        PieceColor::White => 7,
+        PieceColor::Black => 0,
+    }
+}
+
+/// Pushes the pawn move `from` -> `to`, expanded into one move per
+/// promotion piece when `to` is on the last rank.
+fn push_pawn_move(
+    moves: &mut Vec<Move>,
+    from: Square,
+    to: Square,
+    color: PieceColor,
+    flags: MoveFlags,
+) {
+    if to.y == home_row(color.opponent()) {
+        moves.extend(
+            PROMOTION_PIECES
+                .iter()
+                .map(|&piece_type| Move::with_promotion(from, to, piece_type).with_flags(flags)),
+        );
+    } else {
+        moves.push(Move::new(from, to).with_flags(flags));
+    }
+}
+
+/// Rook move of the castling king move `mv`.
+fn rook_move(mv: Move) -> Move {
+    let y = mv.from.y;
+    if mv.to.x > mv.from.x {
+        Move::new(Square::new(7, y), Square::new(5, y))
+    } else {
+        Move::new(Square::new(0, y), Square::new(3, y))
+    }
+}
+
+fn pawn_start_row(color: PieceColor) -> usize {
+    match color {
+        PieceColor::White => 6,
+        PieceColor::Black => 1,
+    }
+}
+
+/// Moves to undo for `color` to take back its last move: one when it has
+/// just moved, two when its opponent has replied since.
+pub fn takeback_plies(board: &Board, history: &MoveHistory, color: PieceColor) -> Option<usize> {
+    let plies = if board.side_to_move == color { 2 } else { 1 };
+    (history.moves.len() >= plies).then_some(plies)
+}
+
+impl Board {
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        self.pieces[square.y][square.x]
+    }
+
+    pub fn king_square(&self, color: PieceColor) -> Option<Square> {
+        squares(self.bitboards.pieces(PieceType::King, color)).next()
+    }
+
+    /// Whether any piece of color `by` attacks `square`, regardless of pins.
+    pub fn is_square_attacked(&self, square: Square, by: PieceColor) -> bool {
+        self.attackers(square, by, self.bitboards.occupied()) != 0
+    }
+
+    /// Pieces of color `by` attacking `square` when the squares in `occupied`
+    /// are the ones taken.
+    fn attackers(&self, square: Square, by: PieceColor, occupied: Bitboard) -> Bitboard {
+        let pieces = |piece_type| self.bitboards.pieces(piece_type, by);
+        let queens = pieces(PieceType::Queen);
+
+        (pawn_attacks(by.opponent(), square) & pieces(PieceType::Pawn))
+            | (knight_attacks(square) & pieces(PieceType::Knight))
+            | (king_attacks(square) & pieces(PieceType::King))
+            | (rook_attacks(square, occupied) & (pieces(PieceType::Rook) | queens))
+            | (bishop_attacks(square, occupied) & (pieces(PieceType::Bishop) | queens))
+    }
+
+    pub fn is_in_check(&self, color: PieceColor) -> bool {
+        match self.king_square(color) {
+            Some(king) => self.is_square_attacked(king, color.opponent()),
+            None => false,
+        }
+    }
+
+    /// Moves of the piece on `from` that follow its movement rules, without
+    /// checking whether they leave its own king in check.
+    pub fn pseudo_legal_moves_from(&self, from: Square) -> Vec<Move> {
+        let mut moves = Vec::new();
+
+        let Some(piece) = self.piece_at(from) else {
+            return moves;
+        };
+
+        let occupied = self.bitboards.occupied();
+        let targets = match piece.piece_type {
+            PieceType::Pawn => {
+                let direction = pawn_direction(piece.color);
+
+                if let Some(one_step) = from.offset(0, direction) {
+                    if occupied & square_bit(one_step) == 0 {
+                        push_pawn_move(&mut moves, from, one_step, piece.color, MoveFlags::QUIET);
+
+                        if from.y == pawn_start_row(piece.color) {
+                            if let Some(two_steps) = from.offset(0, 2 * direction) {
+                                if occupied & square_bit(two_steps) == 0 {
+                                    moves.push(
+                                        Move::new(from, two_steps)
+                                            .with_flags(MoveFlags::DOUBLE_PAWN_PUSH),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+
+                let attacks = pawn_attacks(piece.color, from);
+                let enemies = self.bitboards.color(piece.color.opponent());
+                for target in squares(attacks & enemies) {
+                    push_pawn_move(&mut moves, from, target, piece.color, MoveFlags::CAPTURE);
+                }
+                let en_passant = self
+                    .en_passant
+                    .filter(|&target| attacks & square_bit(target) != 0);
+                if let Some(target) = en_passant {
+                    if self
+                        .en_passant_capture_square(Move::new(from, target))
+                        .is_some()
+                    {
+                        moves.push(
+                            Move::new(from, target)
+                                .with_flags(MoveFlags::CAPTURE | MoveFlags::EN_PASSANT),
+                        );
+                    }
+                }
+
+                return moves;
+            }
+            PieceType::Knight => knight_attacks(from),
+            PieceType::King => {
+                moves.extend(self.castling_moves(from, piece.color));
+                king_attacks(from)
+            }
+            PieceType::Rook => rook_attacks(from, occupied),
+            PieceType::Bishop => bishop_attacks(from, occupied),
+            PieceType::Queen => queen_attacks(from, occupied),
+        };
+
+        let enemies = self.bitboards.color(piece.color.opponent());
+        let targets = targets & !self.bitboards.color(piece.color);
+        moves.extend(squares(targets).map(|to| {
+            let flags = if enemies & square_bit(to) != 0 {
+                MoveFlags::CAPTURE
+            } else {
+                MoveFlags::QUIET
+            };
+            Move::new(from, to).with_flags(flags)
+        }));
+
+        moves
+    }
+
+    pub fn pseudo_legal_moves(&self, color: PieceColor) -> Vec<Move> {
+        squares(self.bitboards.color(color))
+            .flat_map(|from| self.pseudo_legal_moves_from(from))
+            .collect()
+    }
+
+    /// Moves of the piece on `from` that do not leave its own king in check.
+    pub fn legal_moves_from(&self, from: Square) -> Vec<Move> {
+        let Some(piece) = self.piece_at(from) else {
+            return Vec::new();
+        };
+
+        self.pseudo_legal_moves_from(from)
+            .into_iter()
+            .filter(|&mv| !self.leaves_king_in_check(mv, piece.color))
+            .collect()
+    }
+
+    pub fn legal_moves(&self, color: PieceColor) -> Vec<Move> {
+        self.pseudo_legal_moves(color)
+            .into_iter()
+            .filter(|&mv| !self.leaves_king_in_check(mv, color))
+            .collect()
+    }
+
+    /// Whether `mv` is legal for the side to move.
+    pub fn is_legal_move(&self, mv: Move) -> bool {
+        self.piece_at(mv.from)
+            .is_some_and(|piece| piece.color == self.side_to_move)
+            && self.legal_moves_from(mv.from).contains(&mv)
+    }
+
+    /// The rook move that goes along with `mv` if `mv` is a castling king
+    /// move.
+    pub fn castling_rook_move(&self, mv: Move) -> Option<Move> {
+        let piece = self.piece_at(mv.from)?;
+
+        if piece.piece_type != PieceType::King
+            || mv.from.y != mv.to.y
+            || mv.from.x.abs_diff(mv.to.x) != 2
+        {
+            return None;
+        }
+
+        Some(rook_move(mv))
+    }
+
+    /// Square of the pawn captured by `mv` if `mv` is an en passant capture.
+    pub fn en_passant_capture_square(&self, mv: Move) -> Option<Square> {
+        let piece = self.piece_at(mv.from)?;
+
+        if piece.piece_type != PieceType::Pawn
+            || mv.from.x == mv.to.x
+            || self.en_passant != Some(mv.to)
+            || self.piece_at(mv.to).is_some()
+        {
+            return None;
+        }
+
+        let captured = Square::new(mv.to.x, mv.from.y);
+        self.piece_at(captured)
+            .is_some_and(|other| other.piece_type == PieceType::Pawn && other.color != piece.color)
+            .then_some(captured)
+    }
+
+    /// Flags of `mv` in this position, for moves that do not come from
+    /// move generation.
+    pub fn move_flags(&self, mv: Move) -> MoveFlags {
+        let Some(piece) = self.piece_at(mv.from) else {
+            return MoveFlags::QUIET;
+        };
+
+        if self.castling_rook_move(mv).is_some() {
+            MoveFlags::CASTLING
+        } else if self.en_passant_capture_square(mv).is_some() {
+            MoveFlags::CAPTURE | MoveFlags::EN_PASSANT
+        } else if self.piece_at(mv.to).is_some() {
+            MoveFlags::CAPTURE
+        } else if piece.piece_type == PieceType::Pawn && mv.from.y.abs_diff(mv.to.y) == 2 {
+            MoveFlags::DOUBLE_PAWN_PUSH
+        } else {
+            MoveFlags::QUIET
+        }
+    }
+
+    /// Plays `mv` on the board without any legality check, returning the
+    /// captured piece if there was one. The hash is updated piece by piece
+    /// rather than recomputed, and what the move changed is kept for
+    /// `unmake_move`.
+    pub fn make_move(&mut self, mv: Move) -> Option<Piece> {
+        let mv = mv.with_flags(self.move_flags(mv));
+        let mut undo = Undo {
+            mv,
+            moved: None,
+            captured: None,
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            side_to_move: self.side_to_move,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+            position_history_len: self.position_history.len(),
+            cleared_position_history: None,
+        };
+
+        self.hash ^= self.state_key();
+
+        if mv.flags.contains(MoveFlags::CASTLING) {
+            let rook_move = rook_move(mv);
+            let rook = self.take_piece(rook_move.from);
+            self.put_piece(rook_move.to, rook);
+        }
+
+        let en_passant_capture = if mv.flags.contains(MoveFlags::EN_PASSANT) {
+            self.take_piece(Square::new(mv.to.x, mv.from.y))
+        } else {
+            None
+        };
+
+        let piece = self.take_piece(mv.from);
+        let captured = self.take_piece(mv.to).or(en_passant_capture);
+        undo.moved = piece;
+        undo.captured = captured;
+
+        self.en_passant = if mv.flags.contains(MoveFlags::DOUBLE_PAWN_PUSH) {
+            Some(Square::new(mv.from.x, (mv.from.y + mv.to.y) / 2))
+        } else {
+            None
+        };
+
+        if let Some(piece) = piece {
+            if piece.piece_type == PieceType::King {
+                self.castling_rights.remove(piece.color);
+            }
+
+            // Positions before a capture or pawn move cannot come back.
+            if piece.piece_type == PieceType::Pawn || captured.is_some() {
+                self.halfmove_clock = 0;
+                undo.cleared_position_history = Some(std::mem::take(&mut self.position_history));
+            } else {
+                self.halfmove_clock += 1;
+                self.position_history.push(undo.hash);
+            }
+
+            if piece.color == PieceColor::Black {
+                self.fullmove_number += 1;
+            }
+
+            self.side_to_move = piece.color.opponent();
+        }
+
+        for square in [mv.from, mv.to] {
+            match (square.x, square.y) {
+                (0, 7) => self.castling_rights.white_queen_side = false,
+                (7, 7) => self.castling_rights.white_king_side = false,
+                (0, 0) => self.castling_rights.black_queen_side = false,
+                (7, 0) => self.castling_rights.black_king_side = false,
+                _ => {}
+            }
+        }
+
+        let placed = match (piece, mv.promotion) {
+            (Some(piece), Some(piece_type)) => Some(Piece {
+                piece_type,
+                color: piece.color,
+                index: get_piece_index(piece_type, piece.color),
+            }),
+            _ => piece,
+        };
+        self.put_piece(mv.to, placed);
+
+        self.hash ^= self.state_key();
+        self.undo_stack.push(undo);
+
+        captured
+    }
+
+    /// Takes back the last move made with `make_move`, putting back the
+    /// pieces it moved or captured and the castling rights, en passant
+    /// square and counters from before it. Returns the move taken back.
+    pub fn unmake_move(&mut self) -> Option<Move> {
+        let undo = self.undo_stack.pop()?;
+        let mv = undo.mv;
+
+        self.take_piece(mv.to);
+        self.put_piece(mv.from, undo.moved);
+
+        if let Some(captured) = undo.captured {
+            let square = if mv.flags.contains(MoveFlags::EN_PASSANT) {
+                Square::new(mv.to.x, mv.from.y)
+            } else {
+                mv.to
+            };
+            self.put_piece(square, Some(captured));
+        }
+
+        if mv.flags.contains(MoveFlags::CASTLING) {
+            let rook_move = rook_move(mv);
+            let rook = self.take_piece(rook_move.to);
+            self.put_piece(rook_move.from, rook);
+        }
+
+        self.castling_rights = undo.castling_rights;
+        self.en_passant = undo.en_passant;
+        self.side_to_move = undo.side_to_move;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.hash = undo.hash;
+        match undo.cleared_position_history {
+            Some(position_history) => self.position_history = position_history,
+            None => self.position_history.truncate(undo.position_history_len),
+        }
+
+        Some(mv)
+    }
+
+    /// Removes the piece on `square`, taking it out of the bitboards and
+    /// the hash.
+    fn take_piece(&mut self, square: Square) -> Option<Piece> {
+        let piece = self.pieces[square.y][square.x].take();
+        if let Some(piece) = piece {
+            self.bitboards.toggle(piece, square);
+            self.hash ^= piece_key(piece, square);
+        }
+        piece
+    }
+
+    /// Puts `piece` on the empty `square`, adding it to the bitboards and the
+    /// hash.
+    fn put_piece(&mut self, square: Square, piece: Option<Piece>) {
+        if let Some(piece) = piece {
+            self.bitboards.toggle(piece, square);
+            self.hash ^= piece_key(piece, square);
+        }
+        self.pieces[square.y][square.x] = piece;
+    }
+
+    pub fn is_checkmate(&self, color: PieceColor) -> bool {
+        self.is_in_check(color) && self.legal_moves(color).is_empty()
+    }
+
+    pub fn is_stalemate(&self, color: PieceColor) -> bool {
+        !self.is_in_check(color) && self.legal_moves(color).is_empty()
+    }
+
+    /// Result of the game if the side to move has no way to continue, or if
+    /// the rules declare it drawn.
+    pub fn game_result(&self) -> Option<GameResult> {
+        let to_move = self.side_to_move;
+
+        if !self.legal_moves(to_move).is_empty() {
+            return self.automatic_draw().map(GameResult::Draw);
+        }
+
+        if !self.is_in_check(to_move) {
+            return Some(GameResult::Draw(DrawReason::Stalemate));
+        }
+
+        match to_move {
+            PieceColor::White => Some(GameResult::BlackWins(WinReason::Checkmate)),
+            PieceColor::Black => Some(GameResult::WhiteWins(WinReason::Checkmate)),
+        }
+    }
+
+    /// Draw declared whatever the players want: fivefold repetition, the
+    /// seventy-five-move rule or insufficient material.
+    pub fn automatic_draw(&self) -> Option<DrawReason> {
+        if self.repetitions() >= 5 {
+            Some(DrawReason::FivefoldRepetition)
+        } else if self.halfmove_clock >= SEVENTY_FIVE_MOVE_PLIES {
+            Some(DrawReason::SeventyFiveMoveRule)
+        } else if self.is_insufficient_material() {
+            Some(DrawReason::InsufficientMaterial)
+        } else {
+            None
+        }
+    }
+
+    /// Draw the side to move may claim: threefold repetition or the
+    /// fifty-move rule.
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.repetitions() >= 3 {
+            Some(DrawReason::ThreefoldRepetition)
+        } else if self.halfmove_clock >= FIFTY_MOVE_PLIES {
+            Some(DrawReason::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    /// Result of the game when `color` runs out of time: a loss, unless the
+    /// opponent could not mate it whatever the moves played.
+    pub fn timeout_result(&self, color: PieceColor) -> GameResult {
+        if self.has_mating_material(color.opponent()) {
+            GameResult::win(color.opponent(), WinReason::Timeout)
+        } else {
+            GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial)
+        }
+    }
+
+    /// Times the current position has been reached, counting this one.
+    pub fn repetitions(&self) -> usize {
+        1 + self
+            .position_history
+            .iter()
+            .filter(|&&other| other == self.hash)
+            .count()
+    }
+
+    /// Whether neither side can ever mate: only kings are left, with at most
+    /// one knight or bishop, or any number of bishops all on squares of the
+    /// same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut minors = Vec::new();
+
+        for (y, row) in self.pieces.iter().enumerate() {
+            for (x, piece) in row.iter().enumerate() {
+                let Some(piece) = piece else {
+                    continue;
+                };
+
+                match piece.piece_type {
+                    PieceType::King => {}
+                    PieceType::Knight | PieceType::Bishop => {
+                        minors.push((piece.piece_type, (x + y) % 2));
+                    }
+                    _ => return false,
+                }
+            }
+        }
+
+        minors.len() <= 1
+            || minors
+                .iter()
+                .all(|&(piece_type, shade)| piece_type == PieceType::Bishop && shade == minors[0].1)
+    }
+
+    /// Whether `color` could mate by some series of legal moves, the
+    /// opponent's pieces included since they may block their own king. A
+    /// knight needs any other piece on the board to do so, and bishops a
+    /// piece that is not a bishop on the same color of square as theirs.
+    pub fn has_mating_material(&self, color: PieceColor) -> bool {
+        let mut own = Vec::new();
+        let mut others = Vec::new();
+
+        for (y, row) in self.pieces.iter().enumerate() {
+            for (x, piece) in row.iter().enumerate() {
+                let Some(piece) = piece.filter(|piece| piece.piece_type != PieceType::King) else {
+                    continue;
+                };
+
+                let minor = matches!(piece.piece_type, PieceType::Knight | PieceType::Bishop);
+                if piece.color == color && !minor {
+                    return true;
+                }
+
+                let shade = (x + y) % 2;
+                if piece.color == color {
+                    own.push((piece.piece_type, shade));
+                } else {
+                    others.push((piece.piece_type, shade));
+                }
+            }
+        }
+
+        let Some(&(_, shade)) = own.first() else {
+            return false;
+        };
+        if own
+            .iter()
+            .any(|&(piece_type, _)| piece_type == PieceType::Knight)
+        {
+            return own.len() + others.len() > 1;
+        }
+
+        own.iter()
+            .chain(others.iter())
+            .any(|&(piece_type, other_shade)| {
+                piece_type != PieceType::Bishop || other_shade != shade
+            })
+    }
+
+    /// Whether a pawn of the side to move stands next to the square skipped
+    /// by the pawn that just moved two squares, ready to take en passant.
+    pub fn en_passant_available(&self) -> bool {
+        let Some(target) = self.en_passant else {
+            return false;
+        };
+
+        let pawns = self.bitboards.pieces(PieceType::Pawn, self.side_to_move);
+        pawn_attacks(self.side_to_move.opponent(), target) & pawns != 0
+    }
+
+    /// Castling moves of the king of `color` standing on `from`. The king may
+    /// not castle out of, through or into check.
+    fn castling_moves(&self, from: Square, color: PieceColor) -> Vec<Move> {
+        let mut moves = Vec::new();
+
+        let y = home_row(color);
+        if from != Square::new(4, y) {
+            return moves;
+        }
+
+        let opponent = color.opponent();
+        if self.is_square_attacked(from, opponent) {
+            return moves;
+        }
+
+        let is_rook = |x: usize| {
+            self.piece_at(Square::new(x, y))
+                .is_some_and(|piece| piece.piece_type == PieceType::Rook && piece.color == color)
+        };
+        let is_empty = |x: usize| self.piece_at(Square::new(x, y)).is_none();
+        let is_safe = |x: usize| !self.is_square_attacked(Square::new(x, y), opponent);
+
+        if self.castling_rights.king_side(color)
+            && is_rook(7)
+            && is_empty(5)
+            && is_empty(6)
+            && is_safe(5)
+            && is_safe(6)
+        {
+            moves.push(Move::new(from, Square::new(6, y)).with_flags(MoveFlags::CASTLING));
+        }
+
+        if self.castling_rights.queen_side(color)
+            && is_rook(0)
+            && is_empty(1)
+            && is_empty(2)
+            && is_empty(3)
+            && is_safe(3)
+            && is_safe(2)
+        {
+            moves.push(Move::new(from, Square::new(2, y)).with_flags(MoveFlags::CASTLING));
+        }
+
+        moves
+    }
+
+    /// Whether playing `mv` would leave the king of `color` attacked,
+    /// worked out on the bitboards without playing it. The rook of a
+    /// castling move is left where it is, as it never shields the king.
+    fn leaves_king_in_check(&self, mv: Move, color: PieceColor) -> bool {
+        let Some(piece) = self.piece_at(mv.from) else {
+            return false;
+        };
+
+        let captured = square_bit(self.en_passant_capture_square(mv).unwrap_or(mv.to));
+        let occupied =
+            (self.bitboards.occupied() & !square_bit(mv.from) & !captured) | square_bit(mv.to);
+
+        let king = if piece.piece_type == PieceType::King {
+            Some(mv.to)
+        } else {
+            self.king_square(color)
+        };
+
+        king.is_some_and(|king| self.attackers(king, color.opponent(), occupied) & !captured != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name: &str) -> Square {
+        let bytes = name.as_bytes();
+        Square::new((bytes[0] - b'a') as usize, (b'8' - bytes[1]) as usize)
+    }
+
+    fn mv(uci: &str) -> Move {
+        Move::new(square(&uci[..2]), square(&uci[2..]))
+    }
+
+    fn uci(mv: &Move) -> String {
+        let name = |square: Square| format!("{}{}", (b'a' + square.x as u8) as char, 8 - square.y);
+        format!("{}{}", name(mv.from), name(mv.to))
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    fn legal_uci(board: &Board) -> Vec<String> {
+        let mut moves: Vec<String> = board
+            .legal_moves(PieceColor::White)
+            .iter()
+            .map(uci)
+            .collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn start_position_has_twenty_moves() {
+        let board = Board::default();
+        assert_eq!(board.legal_moves(PieceColor::White).len(), 20);
+        assert!(board.is_legal_move(mv("e2e4")));
+        assert!(board.is_legal_move(mv("g1f3")));
+        assert!(!board.is_legal_move(mv("e2e5")));
+    }
+
+    #[test]
+    fn pieces_cannot_capture_their_own_side() {
+        let board = Board::default();
+        assert!(!board.is_legal_move(mv("d1d2")));
+        assert!(!board.is_legal_move(mv("a1a2")));
+        assert!(!board.is_legal_move(mv("e1f1")));
+        assert!(!board.is_legal_move(mv("g1e2")));
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_the_pin_line() {
+        let bishop_pinned = board("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1");
+        assert!(bishop_pinned.legal_moves_from(square("e2")).is_empty());
+
+        let rook_pinned = board("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1");
+        let mut moves: Vec<String> = rook_pinned
+            .legal_moves_from(square("e2"))
+            .iter()
+            .map(uci)
+            .collect();
+        moves.sort();
+        assert_eq!(moves, ["e2e3", "e2e4", "e2e5", "e2e6", "e2e7"]);
+    }
+
+    #[test]
+    fn checks_must_be_answered() {
+        let check = board("4k3/8/8/8/8/8/8/r3K3 w - - 0 1");
+        assert!(check.is_in_check(PieceColor::White));
+        assert_eq!(legal_uci(&check), ["e1d2", "e1e2", "e1f2"]);
+
+        let block = board("4k3/8/8/8/8/8/3N4/r3K3 w - - 0 1");
+        assert_eq!(legal_uci(&block), ["d2b1", "e1e2", "e1f2"]);
+    }
+
+    #[test]
+    fn king_cannot_walk_into_check() {
+        let board = board("4k3/5r2/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(!board.is_legal_move(mv("e1f1")));
+        assert!(!board.is_legal_move(mv("e1f2")));
+        assert!(board.is_legal_move(mv("e1d1")));
+        assert!(board.is_legal_move(mv("e1e2")));
+    }
+
+    #[test]
+    fn cannot_castle_through_an_attacked_square() {
+        let through_check = board("4kr2/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert!(!through_check.is_legal_move(mv("e1g1")));
+
+        let free = board("4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert!(free.is_legal_move(mv("e1g1")));
+    }
+
+    #[test]
+    fn lone_minor_piece_needs_something_to_mate_against() {
+        let knight = board("4k3/8/8/8/8/8/8/3NK3 w - - 0 1");
+        assert!(!knight.has_mating_material(PieceColor::White));
+        assert_eq!(
+            knight.timeout_result(PieceColor::Black),
+            GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial)
+        );
+
+        let knight_against_pawn = board("4k3/4p3/8/8/8/8/8/3NK3 w - - 0 1");
+        assert!(knight_against_pawn.has_mating_material(PieceColor::White));
+        assert_eq!(
+            knight_against_pawn.timeout_result(PieceColor::Black),
+            GameResult::WhiteWins(WinReason::Timeout)
+        );
+
+        assert!(board("4k3/8/8/8/8/8/8/2NNK3 w - - 0 1").has_mating_material(PieceColor::White));
+        assert!(board("4k2r/8/8/8/8/8/8/2B1K3 w - - 0 1").has_mating_material(PieceColor::White));
+    }
+
+    #[test]
+    fn bishops_on_one_color_of_square_cannot_mate_each_other() {
+        let opposite = board("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1");
+        assert!(opposite.has_mating_material(PieceColor::White));
+        assert!(opposite.has_mating_material(PieceColor::Black));
+        assert_eq!(
+            opposite.timeout_result(PieceColor::Black),
+            GameResult::WhiteWins(WinReason::Timeout)
+        );
+
+        let same = board("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1");
+        assert!(!same.has_mating_material(PieceColor::White));
+        assert_eq!(
+            same.timeout_result(PieceColor::Black),
+            GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial)
+        );
+
+        assert!(!board("4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1").has_mating_material(PieceColor::White));
+    }
+
+    #[test]
+    fn flagging_against_a_lone_king_is_a_draw() {
+        let board = board("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+        assert_eq!(
+            board.timeout_result(PieceColor::Black),
+            GameResult::WhiteWins(WinReason::Timeout)
+        );
+        assert_eq!(
+            board.timeout_result(PieceColor::White),
+            GameResult::Draw(DrawReason::TimeoutVsInsufficientMaterial)
+        );
+    }
+
+    #[test]
+    fn unmaking_moves_restores_the_position() {
+        let fen = "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+        let start = board(fen);
+        let line = ["e5d6", "e8g8", "b7a8q", "g8g7", "e1c1", "f8f1", "d1f1"];
+
+        let mut board = start.clone();
+        for uci in line {
+            let mv = Move::from_uci(uci).unwrap();
+            assert!(board.is_legal_move(mv), "{} is illegal", uci);
+            board.make_move(mv);
+        }
+        assert_eq!(board.to_fen(), "Q7/6k1/3P4/8/8/8/8/2K2R1R b - - 0 4");
+
+        for uci in line.iter().rev() {
+            assert_eq!(board.unmake_move(), Move::from_uci(uci));
+        }
+        assert_eq!(board.unmake_move(), None);
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(board.hash, start.hash);
+    }
+}
@@ -0,0 +1,341 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    board::Board,
+    piece::PieceType,
+    rules::{Move, Square},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    Invalid(String),
+    Illegal(String),
+    Ambiguous(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Invalid(text) => write!(f, "'{}' is not a valid SAN move", text),
+            SanError::Illegal(text) => write!(f, "'{}' is not legal in this position", text),
+            SanError::Ambiguous(text) => write!(f, "'{}' matches more than one move", text),
+        }
+    }
+}
+
+impl Error for SanError {}
+
+fn piece_type_from_char(c: char) -> Option<PieceType> {
+    match c {
+        'K' => Some(PieceType::King),
+        'Q' => Some(PieceType::Queen),
+        'R' => Some(PieceType::Rook),
+        'B' => Some(PieceType::Bishop),
+        'N' => Some(PieceType::Knight),
+        _ => None,
+    }
+}
+
+impl Board {
+    /// Formats the legal move `mv` in Standard Algebraic Notation, including
+    /// the check or checkmate suffix.
+    pub fn move_to_san(&self, mv: Move) -> String {
+        let Some(piece) = self.piece_at(mv.from) else {
+            return String::new();
+        };
+
+        let mut san = String::new();
+
+        if let Some(rook_move) = self.castling_rook_move(mv) {
+            san.push_str(if rook_move.from.x == 7 {
+                "O-O"
+            } else {
+                "O-O-O"
+            });
+        } else {
+            let is_capture =
+                self.piece_at(mv.to).is_some() || self.en_passant_capture_square(mv).is_some();
+
+            if piece.piece_type == PieceType::Pawn {
+                if is_capture {
+                    san.push_str(&mv.from.to_string()[..1]);
+                }
+            } else {
+                san.push_str(&piece.piece_type.to_string());
+
+                let rivals: Vec<Square> = self
+                    .legal_moves(piece.color)
+                    .into_iter()
+                    .filter(|other| {
+                        other.to == mv.to
+                            && other.from != mv.from
+                            && self
+                                .piece_at(other.from)
+                                .is_some_and(|other| other.piece_type == piece.piece_type)
+                    })
+                    .map(|other| other.from)
+                    .collect();
+
+                let from = mv.from.to_string();
+                if !rivals.is_empty() {
+                    if rivals.iter().all(|rival| rival.x != mv.from.x) {
+                        san.push_str(&from[..1]);
+                    } else if rivals.iter().all(|rival| rival.y != mv.from.y) {
+                        san.push_str(&from[1..]);
+                    } else {
+                        san.push_str(&from);
+                    }
+                }
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&mv.to.to_string());
+
+            if let Some(piece_type) = mv.promotion {
+                san.push_str(&format!("={}", piece_type));
+            }
+        }
+
+        let mut board = self.clone();
+        board.make_move(mv);
+        if board.is_in_check(board.side_to_move) {
+            san.push(if board.legal_moves(board.side_to_move).is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+
+        san
+    }
+
+    /// Like `move_to_san`, but marks en passant captures with `e.p.` as is
+    /// customary in printed move lists. PGN export must not use it.
+    pub fn move_to_san_with_en_passant(&self, mv: Move) -> String {
+        let san = self.move_to_san(mv);
+
+        if self.en_passant_capture_square(mv).is_some() {
+            format!("{} e.p.", san)
+        } else {
+            san
+        }
+    }
+
+    /// Finds the legal move of the side to move described by `text` in
+    /// Standard Algebraic Notation. Check, annotation and `e.p.` suffixes are
+    /// ignored, and common variants such as `0-0`, `e8Q` or long algebraic
+    /// `Ng1-f3` are accepted as well.
+    pub fn parse_san(&self, text: &str) -> Result<Move, SanError> {
+        let invalid = || SanError::Invalid(text.to_string());
+
+        let san = text.trim();
+        let san = san.strip_suffix("e.p.").unwrap_or(san).trim_end();
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+        let candidates = self.legal_moves(self.side_to_move);
+
+        let castling = san.replace('0', "O");
+
+        let matches: Vec<Move> = if castling == "O-O" || castling == "O-O-O" {
+            let rook_file = if castling == "O-O" { 7 } else { 0 };
+            candidates
+                .into_iter()
+                .filter(|&mv| {
+                    self.castling_rook_move(mv)
+                        .is_some_and(|rook_move| rook_move.from.x == rook_file)
+                })
+                .collect()
+        } else {
+            let mut chars: Vec<char> = san
+                .chars()
+                .filter(|c| !matches!(c, 'x' | ':' | '-' | '='))
+                .collect();
+
+            let piece_type = match chars.first() {
+                Some('P') => {
+                    chars.remove(0);
+                    PieceType::Pawn
+                }
+                Some(&c) => match piece_type_from_char(c) {
+                    Some(piece_type) => {
+                        chars.remove(0);
+                        piece_type
+                    }
+                    None => PieceType::Pawn,
+                },
+                None => return Err(invalid()),
+            };
+
+            let promotion = match chars.as_slice() {
+                [.., rank, last] if rank.is_ascii_digit() => {
+                    piece_type_from_char(last.to_ascii_uppercase())
+                        .filter(|&piece_type| piece_type != PieceType::King)
+                }
+                _ => None,
+            };
+            if promotion.is_some() {
+                chars.pop();
+            }
+
+            if chars.len() < 2 || chars.len() > 4 {
+                return Err(invalid());
+            }
+
+            let destination: String = chars[chars.len() - 2..].iter().collect();
+            let to = Square::from_algebraic(&destination).ok_or_else(invalid)?;
+
+            let mut from_file = None;
+            let mut from_rank = None;
+            for &c in chars[..chars.len() - 2].iter() {
+                match c {
+                    'a'..='h' if from_file.is_none() => from_file = Some(c as usize - 'a' as usize),
+                    '1'..='8' if from_rank.is_none() => from_rank = Some('8' as usize - c as usize),
+                    _ => return Err(invalid()),
+                }
+            }
+
+            candidates
+                .into_iter()
+                .filter(|mv| {
+                    mv.to == to
+                        && mv.promotion == promotion
+                        && from_file.map_or(true, |x| mv.from.x == x)
+                        && from_rank.map_or(true, |y| mv.from.y == y)
+                        && self
+                            .piece_at(mv.from)
+                            .is_some_and(|piece| piece.piece_type == piece_type)
+                })
+                .collect()
+        };
+
+        match matches.as_slice() {
+            [mv] => Ok(*mv),
+            [] => Err(SanError::Illegal(text.to_string())),
+            _ => Err(SanError::Ambiguous(text.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::STARTING_FEN;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    /// Move written in coordinate notation, such as `e2e4` or `e7e8q`.
+    fn mv(uci: &str) -> Move {
+        let from = Square::from_algebraic(&uci[0..2]).unwrap();
+        let to = Square::from_algebraic(&uci[2..4]).unwrap();
+        match uci[4..].chars().next() {
+            Some(c) => Move::with_promotion(
+                from,
+                to,
+                piece_type_from_char(c.to_ascii_uppercase()).unwrap(),
+            ),
+            None => Move::new(from, to),
+        }
+    }
+
+    fn san(fen: &str, uci: &str) -> String {
+        board(fen).move_to_san(mv(uci))
+    }
+
+    fn parse(fen: &str, text: &str) -> Result<Move, SanError> {
+        board(fen).parse_san(text)
+    }
+
+    const TWO_KNIGHTS: &str = "rnbqkb1r/ppp1pppp/5n2/3p4/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 3";
+    const TWO_ROOKS: &str = "7k/8/8/8/8/4R3/8/K3R3 w - - 0 1";
+    const THREE_QUEENS: &str = "7k/8/8/8/8/Q7/8/Q1Q4K w - - 0 1";
+    const EN_PASSANT: &str = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2";
+
+    #[test]
+    fn disambiguates_by_file_then_rank_then_square() {
+        assert_eq!(san(TWO_KNIGHTS, "b8d7"), "Nbd7");
+        assert_eq!(san(TWO_KNIGHTS, "f6d7"), "Nfd7");
+        assert_eq!(san(TWO_ROOKS, "e1e2"), "R1e2");
+        assert_eq!(san(TWO_ROOKS, "e3e2"), "R3e2");
+        assert_eq!(san(THREE_QUEENS, "a1b2"), "Qa1b2+");
+        assert_eq!(san(TWO_ROOKS, "e1d1"), "Rd1");
+    }
+
+    #[test]
+    fn formats_captures_castling_and_promotions() {
+        assert_eq!(san(EN_PASSANT, "e5d6"), "exd6");
+        assert_eq!(
+            board(EN_PASSANT).move_to_san_with_en_passant(mv("e5d6")),
+            "exd6 e.p."
+        );
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1", "e8c8"), "O-O-O");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1", "e8g8"), "O-O");
+        assert_eq!(san("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"), "e8=Q+");
+        assert_eq!(san("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8n"), "e8=N");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn parses_standard_notation() {
+        assert_eq!(parse(TWO_KNIGHTS, "Nbd7").unwrap(), mv("b8d7"));
+        assert_eq!(parse(TWO_ROOKS, "R1e2").unwrap(), mv("e1e2"));
+        assert_eq!(parse(THREE_QUEENS, "Qa1b2").unwrap(), mv("a1b2"));
+        assert_eq!(parse(EN_PASSANT, "exd6").unwrap(), mv("e5d6"));
+        assert_eq!(
+            parse("r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1", "O-O-O").unwrap(),
+            mv("e8c8")
+        );
+        assert_eq!(
+            parse("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e8=Q+").unwrap(),
+            mv("e7e8q")
+        );
+        assert_eq!(
+            parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "Ra8#").unwrap(),
+            mv("a1a8")
+        );
+    }
+
+    #[test]
+    fn parses_common_variants_leniently() {
+        assert_eq!(parse(EN_PASSANT, "exd6 e.p.").unwrap(), mv("e5d6"));
+        assert_eq!(parse(EN_PASSANT, "e5:d6").unwrap(), mv("e5d6"));
+        assert_eq!(
+            parse("r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1", "0-0").unwrap(),
+            mv("e8g8")
+        );
+        assert_eq!(
+            parse("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e8Q").unwrap(),
+            mv("e7e8q")
+        );
+        assert_eq!(
+            parse("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7-e8=N").unwrap(),
+            mv("e7e8n")
+        );
+        assert_eq!(parse(STARTING_FEN, "Ng1-f3!?").unwrap(), mv("g1f3"));
+        assert_eq!(parse(STARTING_FEN, " Pe4 ").unwrap(), mv("e2e4"));
+        assert_eq!(parse(STARTING_FEN, "e2e4").unwrap(), mv("e2e4"));
+    }
+
+    #[test]
+    fn rejects_invalid_illegal_and_ambiguous_moves() {
+        assert_eq!(
+            parse(TWO_KNIGHTS, "Nd7").unwrap_err(),
+            SanError::Ambiguous("Nd7".to_string())
+        );
+        assert_eq!(
+            parse(TWO_KNIGHTS, "Nc5").unwrap_err(),
+            SanError::Illegal("Nc5".to_string())
+        );
+        assert_eq!(
+            parse(TWO_KNIGHTS, "Nz9").unwrap_err(),
+            SanError::Invalid("Nz9".to_string())
+        );
+        assert_eq!(
+            parse(TWO_KNIGHTS, "").unwrap_err(),
+            SanError::Invalid(String::new())
+        );
+    }
+}
@@ -0,0 +1,1032 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::Board,
+    clock::Clock,
+    history::MoveHistory,
+    piece::PieceColor,
+    protocol::{
+        check_version, ChatChannel, ColorPreference, LiveGame, Message, Seek, SeekOptions,
+        PROTOCOL_VERSION,
+    },
+    rules::{takeback_plies, Move},
+    state::{DrawReason, GameResult, WinReason},
+    CLOCK_TICK_MILLIS, MAX_CHAT_LENGTH, MAX_LAG_COMPENSATION_MILLIS, PING_INTERVAL_SECS,
+    RECONNECT_GRACE_SECS, WRITE_TIMEOUT_SECS,
+};
+
+type ClientId = u64;
+type GameId = u64;
+
+/// Authoritative game server. Players meet in a lobby where they create and
+/// accept seeks or watch live games, and each game's `Board` lives here: a
+/// move only reaches the players and spectators once it has been checked
+/// against the rules.
+///
+/// A player whose connection drops mid-game has `grace_period` to come back
+/// with the session token of the game before losing it.
+///
+/// Messages are queued for each connection and written by a thread of its
+/// own, so that a player who stops reading never holds up the others. A
+/// connection whose writes time out is closed.
+///
+/// Clocks are kept here too. Players are pinged regularly, and the lag this
+/// measures is given back to them on each move, up to
+/// `MAX_LAG_COMPENSATION_MILLIS` and never more than the move took.
+#[derive(Clone)]
+pub struct Server {
+    state: Arc<Mutex<ServerState>>,
+}
+
+struct ServerState {
+    starting_position: MoveHistory,
+    grace_period: Duration,
+    next_client_id: ClientId,
+    next_game_id: GameId,
+    next_seek_id: u64,
+    clients: HashMap<ClientId, Client>,
+    /// Players in a game by session token.
+    sessions: HashMap<String, ClientId>,
+    /// Players who said hello and are neither playing nor watching.
+    lobby: HashSet<ClientId>,
+    seeks: Vec<OpenSeek>,
+    games: HashMap<GameId, Game>,
+    players: HashMap<ClientId, (GameId, PieceColor)>,
+    spectators: HashMap<ClientId, GameId>,
+    next_ping: Instant,
+    next_ping_id: u64,
+}
+
+struct Client {
+    /// `None` while a player in a game is disconnected.
+    outbox: Option<Outbox>,
+    /// Id the current connection was given when accepted, which differs
+    /// from the player's once they resumed a game.
+    connection: ClientId,
+    disconnected_since: Option<Instant>,
+    /// Ping waiting for its pong, and when it was sent.
+    ping: Option<(u64, Instant)>,
+    /// Round trip time to the player, smoothed over the last pings.
+    lag: Duration,
+    /// Round trip time of the last ping answered.
+    last_round_trip: Duration,
+}
+
+/// Sending half of a connection: lines pushed to `sender` are written to
+/// `stream` by a dedicated thread.
+struct Outbox {
+    sender: mpsc::Sender<String>,
+    stream: TcpStream,
+}
+
+struct OpenSeek {
+    id: u64,
+    owner: ClientId,
+    options: SeekOptions,
+}
+
+struct Game {
+    board: Board,
+    history: MoveHistory,
+    white: ClientId,
+    black: ClientId,
+    rated: bool,
+    clock: Clock,
+    /// When `clock` was last brought up to date.
+    last_tick: Instant,
+    spectators: HashSet<ClientId>,
+    /// Player who offered a draw their opponent has not answered yet.
+    draw_offer: Option<PieceColor>,
+    /// Player who asked for a takeback their opponent has not answered yet.
+    takeback_request: Option<PieceColor>,
+}
+
+impl Server {
+    /// Creates a server whose games all start from `starting_position`.
+    pub fn new(starting_position: MoveHistory) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ServerState {
+                starting_position,
+                grace_period: Duration::from_secs(RECONNECT_GRACE_SECS),
+                next_client_id: 0,
+                next_game_id: 0,
+                next_seek_id: 0,
+                clients: HashMap::new(),
+                sessions: HashMap::new(),
+                lobby: HashSet::new(),
+                seeks: Vec::new(),
+                games: HashMap::new(),
+                players: HashMap::new(),
+                spectators: HashMap::new(),
+                next_ping: Instant::now(),
+                next_ping_id: 0,
+            })),
+        }
+    }
+
+    /// Sets how long a disconnected player's game is held for them.
+    pub fn with_grace_period(self, grace_period: Duration) -> Self {
+        self.state.lock().unwrap().grace_period = grace_period;
+        self
+    }
+
+    /// Accepts players on `listener` forever, one thread per connection.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        let server = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(CLOCK_TICK_MILLIS));
+            server.state.lock().unwrap().tick();
+        });
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || server.handle_client(stream));
+        }
+
+        Ok(())
+    }
+
+    fn handle_client(&self, stream: TcpStream) {
+        let Ok(outbox) = Outbox::new(&stream) else {
+            return;
+        };
+        let connection = self.state.lock().unwrap().connect(outbox);
+        let mut client = connection;
+        let mut greeted = false;
+
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            let message = Message::decode(&line);
+            let mut state = self.state.lock().unwrap();
+            match message {
+                Ok(Message::Hello { version }) if !greeted => match check_version(version) {
+                    Ok(()) => {
+                        greeted = true;
+                        state.join(client);
+                    }
+                    Err(error) => {
+                        state.send(client, &Message::error(error.to_string()));
+                        break;
+                    }
+                },
+                Ok(_) if !greeted => {
+                    state.send(client, &Message::error("Expected a hello message"));
+                    break;
+                }
+                Ok(Message::Resume { token }) => {
+                    if let Some(resumed) = state.resume(client, &token) {
+                        client = resumed;
+                    }
+                }
+                Ok(Message::CreateSeek { options }) => state.create_seek(client, options),
+                Ok(Message::CancelSeek) => state.cancel_seek(client),
+                Ok(Message::AcceptSeek { id }) => state.accept_seek(client, id),
+                Ok(Message::Watch { id }) => state.watch(client, id),
+                Ok(Message::StopWatching) => state.stop_watching(client),
+                Ok(Message::Move { mv }) => state.play(client, mv),
+                Ok(Message::Resign) => state.resign(client),
+                Ok(Message::DrawOffer) => state.offer_draw(client),
+                Ok(Message::ClaimDraw) => state.claim_draw(client),
+                Ok(Message::DeclineDraw) => state.decline_draw(client),
+                Ok(Message::Takeback) => state.request_takeback(client),
+                Ok(Message::DeclineTakeback) => state.decline_takeback(client),
+                Ok(Message::SendChat { channel, text }) => state.chat(client, channel, &text),
+                Ok(Message::Pong { id }) => state.pong(client, id),
+                Ok(_) => state.send(client, &Message::error("Unexpected message")),
+                Err(error) => state.send(client, &Message::error(error.to_string())),
+            }
+        }
+
+        if self.state.lock().unwrap().disconnect(client, connection) {
+            let server = self.clone();
+            thread::spawn(move || {
+                let grace_period = server.state.lock().unwrap().grace_period;
+                thread::sleep(grace_period);
+                server.state.lock().unwrap().expire(client);
+            });
+        }
+    }
+}
+
+impl Outbox {
+    /// Starts the thread writing to `stream`, which stops once the outbox is
+    /// dropped or a write fails. A failed write also shuts the connection
+    /// down, for its reader to notice and disconnect the player.
+    fn new(stream: &TcpStream) -> io::Result<Self> {
+        let mut writer = stream.try_clone()?;
+        writer.set_write_timeout(Some(Duration::from_secs(WRITE_TIMEOUT_SECS)))?;
+        let (sender, receiver) = mpsc::channel::<String>();
+
+        thread::spawn(move || {
+            for line in receiver {
+                if writeln!(writer, "{}", line).is_err() {
+                    let _ = writer.shutdown(Shutdown::Both);
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            sender,
+            stream: stream.try_clone()?,
+        })
+    }
+}
+
+impl ServerState {
+    fn send(&mut self, client: ClientId, message: &Message) {
+        if let Some(outbox) = self
+            .clients
+            .get(&client)
+            .and_then(|client| client.outbox.as_ref())
+        {
+            let _ = outbox.sender.send(message.encode());
+        }
+    }
+
+    fn connect(&mut self, outbox: Outbox) -> ClientId {
+        let client = self.next_client_id;
+        self.next_client_id += 1;
+        self.clients.insert(
+            client,
+            Client {
+                outbox: Some(outbox),
+                connection: client,
+                disconnected_since: None,
+                ping: None,
+                lag: Duration::ZERO,
+                last_round_trip: Duration::ZERO,
+            },
+        );
+        self.send(
+            client,
+            &Message::Hello {
+                version: PROTOCOL_VERSION,
+            },
+        );
+
+        client
+    }
+
+    /// Sends the open seeks and the games being played to every player in
+    /// the lobby.
+    fn send_lobby(&mut self) {
+        let mut games: Vec<LiveGame> = self
+            .games
+            .iter()
+            .map(|(&id, game)| LiveGame {
+                id,
+                time_control: game.clock.time_control().clone(),
+                rated: game.rated,
+                spectators: game.spectators.len(),
+            })
+            .collect();
+        games.sort_by_key(|game| game.id);
+
+        let lobby: Vec<ClientId> = self.lobby.iter().copied().collect();
+        for client in lobby {
+            let seeks = self
+                .seeks
+                .iter()
+                .map(|seek| Seek {
+                    id: seek.id,
+                    options: seek.options.clone(),
+                    mine: seek.owner == client,
+                })
+                .collect();
+            let games = games.clone();
+            self.send(client, &Message::Lobby { seeks, games });
+        }
+    }
+
+    fn join(&mut self, client: ClientId) {
+        self.lobby.insert(client);
+        self.send_lobby();
+    }
+
+    fn create_seek(&mut self, client: ClientId, options: SeekOptions) {
+        if !self.lobby.contains(&client) {
+            self.send(client, &Message::error("You are already playing"));
+            return;
+        }
+        if !options.time_control.is_valid() {
+            self.send(client, &Message::error("Invalid time control"));
+            return;
+        }
+
+        let id = self.next_seek_id;
+        self.next_seek_id += 1;
+
+        self.seeks.retain(|seek| seek.owner != client);
+        self.seeks.push(OpenSeek {
+            id,
+            owner: client,
+            options,
+        });
+        self.send_lobby();
+    }
+
+    fn cancel_seek(&mut self, client: ClientId) {
+        self.seeks.retain(|seek| seek.owner != client);
+        self.send_lobby();
+    }
+
+    fn accept_seek(&mut self, client: ClientId, id: u64) {
+        let Some(seek) = self.seeks.iter().find(|seek| seek.id == id) else {
+            self.send(client, &Message::error("This seek is no longer open"));
+            return;
+        };
+        if seek.owner == client || !self.lobby.contains(&client) {
+            self.send(client, &Message::error("You cannot accept this seek"));
+            return;
+        }
+
+        let (owner, options) = (seek.owner, seek.options.clone());
+        let owner_plays_white = match options.color {
+            ColorPreference::White => true,
+            ColorPreference::Black => false,
+            ColorPreference::Random => rand::random(),
+        };
+
+        if owner_plays_white {
+            self.start_game(owner, client, options);
+        } else {
+            self.start_game(client, owner, options);
+        }
+    }
+
+    fn start_game(&mut self, white: ClientId, black: ClientId, options: SeekOptions) {
+        let id = self.next_game_id;
+        self.next_game_id += 1;
+
+        let history = self.starting_position.clone();
+        let board = history.current_position();
+        let mut clock = Clock::new(options.time_control.clone());
+        clock.start(board.side_to_move);
+
+        self.games.insert(
+            id,
+            Game {
+                board,
+                history: history.clone(),
+                white,
+                black,
+                rated: options.rated,
+                clock,
+                last_tick: Instant::now(),
+                spectators: HashSet::new(),
+                draw_offer: None,
+                takeback_request: None,
+            },
+        );
+
+        for (client, color) in [(white, PieceColor::White), (black, PieceColor::Black)] {
+            self.players.insert(client, (id, color));
+            self.sessions.insert(new_token(), client);
+            self.lobby.remove(&client);
+            self.seeks.retain(|seek| seek.owner != client);
+            self.send_start(client);
+        }
+
+        eprintln!("Game {} started ({})", id, options.time_control);
+        self.send_lobby();
+    }
+
+    /// Sends `client` the game it plays in as it currently stands.
+    fn send_start(&mut self, client: ClientId) {
+        let Some(&(id, color)) = self.players.get(&client) else {
+            return;
+        };
+        let Some(game) = self.games.get(&id) else {
+            return;
+        };
+        let Some(token) = self
+            .sessions
+            .iter()
+            .find_map(|(token, &player)| (player == client).then(|| token.clone()))
+        else {
+            return;
+        };
+
+        let start = Message::Start {
+            color,
+            time_control: game.clock.time_control().clone(),
+            rated: game.rated,
+            token,
+            history: Box::new(game.history.clone()),
+        };
+        let clock_sync = game.clock_sync();
+        self.send(client, &start);
+        self.send(client, &clock_sync);
+    }
+
+    /// Hands the connection of `client` over to the player holding `token`,
+    /// returning that player. Their previous connection is closed in case
+    /// it is still open on this side. Only connections waiting in the lobby
+    /// may resume a game, so that no one leaves a game of their own behind.
+    fn resume(&mut self, client: ClientId, token: &str) -> Option<ClientId> {
+        if !self.lobby.contains(&client) {
+            self.send(
+                client,
+                &Message::error("You cannot resume a game right now"),
+            );
+            return None;
+        }
+        let Some(&player) = self.sessions.get(token) else {
+            self.send(
+                client,
+                &Message::error("This game can no longer be resumed"),
+            );
+            return None;
+        };
+
+        let outbox = self
+            .clients
+            .remove(&client)
+            .and_then(|client| client.outbox);
+        self.lobby.remove(&client);
+        self.cancel_seek(client);
+
+        if let Some(player_client) = self.clients.get_mut(&player) {
+            if let Some(previous) = player_client.outbox.take() {
+                let _ = previous.stream.shutdown(Shutdown::Both);
+            }
+            player_client.outbox = outbox;
+            player_client.connection = client;
+            player_client.disconnected_since = None;
+        }
+
+        self.send_start(player);
+        if let Some(opponent) = self.opponent(player) {
+            self.send(opponent, &Message::OpponentReconnected);
+        }
+
+        Some(player)
+    }
+
+    /// Adds `client` to the spectators of game `id` and sends them the game
+    /// as it currently stands.
+    fn watch(&mut self, client: ClientId, id: GameId) {
+        if !self.lobby.contains(&client) {
+            self.send(client, &Message::error("You cannot watch a game right now"));
+            return;
+        }
+        let Some(game) = self.games.get_mut(&id) else {
+            self.send(client, &Message::error("This game is over"));
+            return;
+        };
+
+        game.spectators.insert(client);
+        let spectate = Message::Spectate {
+            id,
+            time_control: game.clock.time_control().clone(),
+            rated: game.rated,
+            history: Box::new(game.history.clone()),
+        };
+        let clock_sync = game.clock_sync();
+
+        self.spectators.insert(client, id);
+        self.lobby.remove(&client);
+        self.cancel_seek(client);
+        self.send(client, &spectate);
+        self.send(client, &clock_sync);
+    }
+
+    fn stop_watching(&mut self, client: ClientId) {
+        let Some(id) = self.spectators.remove(&client) else {
+            return;
+        };
+
+        if let Some(game) = self.games.get_mut(&id) {
+            game.spectators.remove(&client);
+        }
+        self.lobby.insert(client);
+        self.send_lobby();
+    }
+
+    /// Everyone following game `id`: both players then the spectators.
+    fn audience(&self, id: GameId) -> Vec<ClientId> {
+        let Some(game) = self.games.get(&id) else {
+            return Vec::new();
+        };
+
+        [game.white, game.black]
+            .into_iter()
+            .chain(game.spectators.iter().copied())
+            .collect()
+    }
+
+    fn opponent(&self, client: ClientId) -> Option<ClientId> {
+        let &(id, color) = self.players.get(&client)?;
+        let game = self.games.get(&id)?;
+
+        Some(game.player(color.opponent()))
+    }
+
+    fn play(&mut self, client: ClientId, mv: Move) {
+        let Some(&(id, color)) = self.players.get(&client) else {
+            self.send(client, &Message::error("You are not in a game"));
+            return;
+        };
+        let credit = self.lag_credit(client);
+        let Some(game) = self.games.get_mut(&id) else {
+            return;
+        };
+
+        if game.board.side_to_move != color {
+            self.send(client, &Message::error("It is not your turn"));
+            return;
+        }
+
+        game.tick_clock();
+        if game.flag(credit).is_some() {
+            let result = game.board.timeout_result(color);
+            self.end_game(id, result);
+            return;
+        }
+
+        if !game.board.is_legal_move(mv) {
+            self.send(client, &Message::error(format!("Illegal move {}", mv)));
+            return;
+        }
+
+        game.board.make_move(mv);
+        game.history.moves.push(mv);
+        game.draw_offer = None;
+        game.takeback_request = None;
+        game.clock.refund(credit);
+        game.clock.press();
+
+        let result = game.board.game_result();
+        let clock_sync = game.clock_sync();
+
+        for client in self.audience(id) {
+            self.send(client, &Message::Move { mv });
+            self.send(client, &clock_sync);
+        }
+
+        if let Some(result) = result {
+            self.end_game(id, result);
+        }
+    }
+
+    fn resign(&mut self, client: ClientId) {
+        if let Some(&(id, color)) = self.players.get(&client) {
+            self.end_game(
+                id,
+                GameResult::win(color.opponent(), WinReason::Resignation),
+            );
+        }
+    }
+
+    /// Forwards a draw offer to the opponent, or ends the game if the
+    /// opponent had offered one already.
+    fn offer_draw(&mut self, client: ClientId) {
+        let Some(&(id, color)) = self.players.get(&client) else {
+            return;
+        };
+        let Some(game) = self.games.get_mut(&id) else {
+            return;
+        };
+
+        if game.draw_offer == Some(color.opponent()) {
+            self.end_game(id, GameResult::Draw(DrawReason::Agreement));
+            return;
+        }
+
+        game.draw_offer = Some(color);
+        let opponent = game.player(color.opponent());
+        self.send(opponent, &Message::DrawOffer);
+    }
+
+    /// Ends the game of `client` in a draw if the position lets them claim
+    /// one.
+    fn claim_draw(&mut self, client: ClientId) {
+        let Some(&(id, color)) = self.players.get(&client) else {
+            return;
+        };
+        let Some(game) = self.games.get(&id) else {
+            return;
+        };
+
+        match game.board.claimable_draw() {
+            Some(reason) if game.board.side_to_move == color => {
+                self.end_game(id, GameResult::Draw(reason));
+            }
+            _ => self.send(client, &Message::error("You cannot claim a draw now")),
+        }
+    }
+
+    fn decline_draw(&mut self, client: ClientId) {
+        let Some(&(id, color)) = self.players.get(&client) else {
+            return;
+        };
+        let Some(game) = self.games.get_mut(&id) else {
+            return;
+        };
+
+        if game.draw_offer == Some(color.opponent()) {
+            game.draw_offer = None;
+            let opponent = game.player(color.opponent());
+            self.send(opponent, &Message::DeclineDraw);
+        }
+    }
+
+    /// Asks the opponent of `client` to let them take back their last move,
+    /// or takes back the move the opponent asked for.
+    fn request_takeback(&mut self, client: ClientId) {
+        let Some(&(id, color)) = self.players.get(&client) else {
+            return;
+        };
+        let Some(game) = self.games.get_mut(&id) else {
+            return;
+        };
+
+        if game.takeback_request != Some(color.opponent()) {
+            if takeback_plies(&game.board, &game.history, color).is_none() {
+                self.send(client, &Message::error("You have no move to take back"));
+                return;
+            }
+
+            game.takeback_request = Some(color);
+            let opponent = game.player(color.opponent());
+            self.send(opponent, &Message::Takeback);
+            return;
+        }
+
+        let Some(plies) = takeback_plies(&game.board, &game.history, color.opponent()) else {
+            return;
+        };
+        game.tick_clock();
+        for _ in 0..plies {
+            game.board.unmake_move();
+            game.history.moves.pop();
+            game.clock.take_back(game.board.side_to_move);
+        }
+        game.draw_offer = None;
+        game.takeback_request = None;
+        game.clock.stop();
+        game.clock.start(game.board.side_to_move);
+        let clock_sync = game.clock_sync();
+
+        for client in self.audience(id) {
+            self.send(client, &Message::TakenBack { plies });
+            self.send(client, &clock_sync);
+        }
+    }
+
+    fn decline_takeback(&mut self, client: ClientId) {
+        let Some(&(id, color)) = self.players.get(&client) else {
+            return;
+        };
+        let Some(game) = self.games.get_mut(&id) else {
+            return;
+        };
+
+        if game.takeback_request == Some(color.opponent()) {
+            game.takeback_request = None;
+            let opponent = game.player(color.opponent());
+            self.send(opponent, &Message::DeclineTakeback);
+        }
+    }
+
+    /// Relays a chat line to everyone reading `channel` of the game `client`
+    /// follows. Players talk in the game channel, which spectators can read,
+    /// while the spectator channel stays hidden from the players.
+    fn chat(&mut self, client: ClientId, channel: ChatChannel, text: &str) {
+        let text = text.trim();
+        if text.is_empty() || text.chars().count() > MAX_CHAT_LENGTH {
+            let error = format!("Chat messages must be 1 to {} characters", MAX_CHAT_LENGTH);
+            self.send(client, &Message::error(error));
+            return;
+        }
+
+        let (id, from) = match (self.players.get(&client), self.spectators.get(&client)) {
+            (Some(&(id, color)), _) if channel == ChatChannel::Game => (id, color.to_string()),
+            (_, Some(&id)) if channel == ChatChannel::Spectators => {
+                (id, format!("Spectator {}", client))
+            }
+            (Some(_), _) => {
+                self.send(
+                    client,
+                    &Message::error("Only spectators can read this channel"),
+                );
+                return;
+            }
+            (_, Some(_)) => {
+                self.send(
+                    client,
+                    &Message::error("Spectators cannot talk to the players"),
+                );
+                return;
+            }
+            (None, None) => {
+                self.send(client, &Message::error("You are not following a game"));
+                return;
+            }
+        };
+
+        let readers = match channel {
+            ChatChannel::Game => self.audience(id),
+            ChatChannel::Spectators => self
+                .games
+                .get(&id)
+                .map(|game| game.spectators.iter().copied().collect())
+                .unwrap_or_default(),
+        };
+        for reader in readers {
+            let message = Message::Chat {
+                channel,
+                from: from.clone(),
+                text: text.to_string(),
+                mine: reader == client,
+            };
+            self.send(reader, &message);
+        }
+    }
+
+    /// Tells everyone following game `id` how it ended and sends them back to
+    /// the lobby.
+    fn end_game(&mut self, id: GameId, result: GameResult) {
+        let audience = self.audience(id);
+        let Some(mut game) = self.games.remove(&id) else {
+            return;
+        };
+
+        game.tick_clock();
+        game.clock.stop();
+        let clock_sync = game.clock_sync();
+
+        eprintln!("Game {} over: {}", id, result);
+
+        for client in audience {
+            self.send(client, &clock_sync);
+            self.send(client, &Message::GameEnd { result });
+            self.players.remove(&client);
+            self.spectators.remove(&client);
+            self.sessions.retain(|_, &mut player| player != client);
+
+            let connected = self
+                .clients
+                .get(&client)
+                .is_some_and(|client| client.outbox.is_some());
+            if connected {
+                self.lobby.insert(client);
+            } else {
+                self.clients.remove(&client);
+            }
+        }
+        self.send_lobby();
+    }
+
+    /// Runs the clocks, ending the games of players out of time, and pings
+    /// the players every `PING_INTERVAL_SECS`.
+    fn tick(&mut self) {
+        for game in self.games.values_mut() {
+            game.tick_clock();
+        }
+
+        let flagged: Vec<(GameId, GameResult)> = self
+            .games
+            .iter()
+            .filter_map(|(&id, game)| {
+                let running = game.clock.running()?;
+                let color = game.flag(self.lag_credit(game.player(running)))?;
+                Some((id, game.board.timeout_result(color)))
+            })
+            .collect();
+        for (id, result) in flagged {
+            self.end_game(id, result);
+        }
+
+        let now = Instant::now();
+        if now < self.next_ping {
+            return;
+        }
+        self.next_ping = now + Duration::from_secs(PING_INTERVAL_SECS);
+
+        let players: Vec<ClientId> = self.players.keys().copied().collect();
+        for player in players {
+            let id = self.next_ping_id;
+            self.next_ping_id += 1;
+
+            if let Some(client) = self.clients.get_mut(&player) {
+                client.ping = Some((id, now));
+            }
+            self.send(player, &Message::Ping { id });
+        }
+    }
+
+    /// Records the round trip of the ping `id`. A pong only counts for the
+    /// last ping sent: one coming after the next ping went out is ignored.
+    fn pong(&mut self, client: ClientId, id: u64) {
+        let Some(client) = self.clients.get_mut(&client) else {
+            return;
+        };
+        let Some((ping, sent)) = client.ping else {
+            return;
+        };
+        if ping != id {
+            return;
+        }
+
+        let round_trip = sent.elapsed();
+        client.ping = None;
+        client.last_round_trip = round_trip;
+        client.lag = if client.lag.is_zero() {
+            round_trip
+        } else {
+            (client.lag * 3 + round_trip) / 4
+        };
+    }
+
+    /// Time given back to `client` for the lag of a move: the smaller of
+    /// its smoothed and last round trips, so that a player cannot build up
+    /// credit by holding back a few pongs. `Clock::refund` further limits it
+    /// to the time the move took.
+    fn lag_credit(&self, client: ClientId) -> Duration {
+        self.clients
+            .get(&client)
+            .map_or(Duration::ZERO, |client| {
+                client.lag.min(client.last_round_trip)
+            })
+            .min(Duration::from_millis(MAX_LAG_COMPENSATION_MILLIS))
+    }
+
+    /// Forgets about `client`, unless it is in a game: the game is then held
+    /// until `expire` is called once the grace period is over, which is what
+    /// the returned value tells. Nothing happens if `connection` was
+    /// replaced by a resumed one in the meantime.
+    fn disconnect(&mut self, client: ClientId, connection: ClientId) -> bool {
+        let current = self.clients.get(&client).map(|client| client.connection);
+        if current != Some(connection) {
+            return false;
+        }
+
+        self.lobby.remove(&client);
+        self.cancel_seek(client);
+
+        if let Some(id) = self.spectators.remove(&client) {
+            if let Some(game) = self.games.get_mut(&id) {
+                game.spectators.remove(&client);
+            }
+        }
+
+        if !self.players.contains_key(&client) {
+            self.clients.remove(&client);
+            return false;
+        }
+
+        if let Some(client) = self.clients.get_mut(&client) {
+            client.outbox = None;
+            client.disconnected_since = Some(Instant::now());
+        }
+
+        if let Some(opponent) = self.opponent(client) {
+            let grace_secs = self.grace_period.as_secs();
+            self.send(opponent, &Message::OpponentDisconnected { grace_secs });
+        }
+
+        true
+    }
+
+    /// Ends the game of `client` if it has been disconnected for longer
+    /// than the grace period.
+    fn expire(&mut self, client: ClientId) {
+        let expired = self.clients.get(&client).is_some_and(|client| {
+            client
+                .disconnected_since
+                .is_some_and(|since| since.elapsed() >= self.grace_period)
+        });
+
+        if let Some(&(id, color)) = self.players.get(&client).filter(|_| expired) {
+            self.end_game(
+                id,
+                GameResult::win(color.opponent(), WinReason::Abandonment),
+            );
+        }
+    }
+}
+
+impl Game {
+    fn player(&self, color: PieceColor) -> ClientId {
+        match color {
+            PieceColor::White => self.white,
+            PieceColor::Black => self.black,
+        }
+    }
+
+    /// Brings the clock up to date.
+    fn tick_clock(&mut self) {
+        let now = Instant::now();
+        self.clock.tick(now - self.last_tick);
+        self.last_tick = now;
+    }
+
+    /// Side out of time even once `credit` is given back to it.
+    fn flag(&self, credit: Duration) -> Option<PieceColor> {
+        let mut clock = self.clock.clone();
+        clock.refund(credit);
+        clock.flag()
+    }
+
+    fn clock_sync(&self) -> Message {
+        Message::ClockSync {
+            white_ms: self.clock.remaining(PieceColor::White).as_millis() as u64,
+            black_ms: self.clock.remaining(PieceColor::Black).as_millis() as u64,
+            running: self.clock.running(),
+        }
+    }
+}
+
+/// Random token identifying a player in a game, for them to resume it after
+/// a disconnection.
+fn new_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Connects a new player to `state` and has them say hello, returning
+    /// them along with the far end of their connection.
+    fn join(state: &mut ServerState) -> (ClientId, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (accepted, _) = listener.accept().unwrap();
+
+        let client = state.connect(Outbox::new(&accepted).unwrap());
+        state.join(client);
+        (client, stream)
+    }
+
+    fn start_game(state: &mut ServerState, white: ClientId, black: ClientId) {
+        let options = SeekOptions {
+            time_control: "40/90, 30+30".parse().unwrap(),
+            color: ColorPreference::White,
+            rated: false,
+        };
+        state.create_seek(white, options);
+        let id = state.seeks.last().unwrap().id;
+        state.accept_seek(black, id);
+    }
+
+    fn token(state: &ServerState, player: ClientId) -> String {
+        state
+            .sessions
+            .iter()
+            .find_map(|(token, &client)| (client == player).then(|| token.clone()))
+            .unwrap()
+    }
+
+    #[test]
+    fn players_cannot_resume_their_own_game() {
+        let server = Server::new(MoveHistory::default());
+        let mut state = server.state.lock().unwrap();
+        let (white, _white_stream) = join(&mut state);
+        let (black, _black_stream) = join(&mut state);
+        start_game(&mut state, white, black);
+
+        let token = token(&state, white);
+        assert_eq!(state.resume(white, &token), None);
+        assert!(state.clients[&white].outbox.is_some());
+        assert_eq!(state.sessions.get(&token), Some(&white));
+    }
+
+    #[test]
+    fn players_cannot_take_over_another_game() {
+        let server = Server::new(MoveHistory::default());
+        let mut state = server.state.lock().unwrap();
+        let mut streams = Vec::new();
+        let mut clients = Vec::new();
+        for _ in 0..4 {
+            let (client, stream) = join(&mut state);
+            clients.push(client);
+            streams.push(stream);
+        }
+        start_game(&mut state, clients[0], clients[1]);
+        start_game(&mut state, clients[2], clients[3]);
+
+        let token = token(&state, clients[3]);
+        assert_eq!(state.resume(clients[0], &token), None);
+        assert!(state.clients.contains_key(&clients[0]));
+        assert_eq!(state.clients[&clients[3]].connection, clients[3]);
+
+        let (newcomer, _newcomer_stream) = join(&mut state);
+        assert_eq!(state.resume(newcomer, &token), Some(clients[3]));
+        assert!(!state.clients.contains_key(&newcomer));
+        assert_eq!(state.clients[&clients[3]].connection, newcomer);
+    }
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+
+use crate::SIDE_PANEL_WIDTH;
+
+pub struct SidePanelPlugin;
+
+/// Column on the right of the board that game widgets add their sections to.
+#[derive(Component)]
+pub struct SidePanel;
+
+impl Plugin for SidePanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_side_panel);
+    }
+}
+
+pub fn setup_side_panel(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.0),
+                right: Val::Px(0.0),
+                bottom: Val::Px(0.0),
+                width: Val::Px(SIDE_PANEL_WIDTH),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(12.0)),
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            ..default()
+        },
+        SidePanel,
+    ));
+}
@@ -1,4 +1,9 @@
+use std::fmt;
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::piece::PieceColor;
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum GameState {
@@ -8,4 +13,90 @@ pub enum GameState {
     GameInitResources,
     GameInitEntities,
     InGame,
+    GameOver(GameResult),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum GameResult {
+    WhiteWins(WinReason),
+    BlackWins(WinReason),
+    Draw(DrawReason),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum WinReason {
+    Checkmate,
+    Resignation,
+    /// The opponent left the game.
+    Abandonment,
+    /// The opponent ran out of time.
+    Timeout,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum DrawReason {
+    Stalemate,
+    Agreement,
+    InsufficientMaterial,
+    /// Claimed by a player.
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    /// Claimed by a player.
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    /// A player ran out of time but the opponent could not have mated.
+    TimeoutVsInsufficientMaterial,
+}
+
+/// Run condition matching `GameState::GameOver` whatever its result.
+pub fn game_over(state: Res<State<GameState>>) -> bool {
+    matches!(state.get(), GameState::GameOver(_))
+}
+
+impl GameResult {
+    /// Result of a game won by `winner`.
+    pub fn win(winner: PieceColor, reason: WinReason) -> Self {
+        match winner {
+            PieceColor::White => GameResult::WhiteWins(reason),
+            PieceColor::Black => GameResult::BlackWins(reason),
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::WhiteWins(reason) => write!(f, "{} - White wins", reason),
+            GameResult::BlackWins(reason) => write!(f, "{} - Black wins", reason),
+            GameResult::Draw(reason) => write!(f, "Draw by {}", reason),
+        }
+    }
+}
+
+impl fmt::Display for WinReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WinReason::Checkmate => write!(f, "Checkmate"),
+            WinReason::Resignation => write!(f, "Resignation"),
+            WinReason::Abandonment => write!(f, "Abandonment"),
+            WinReason::Timeout => write!(f, "Timeout"),
+        }
+    }
+}
+
+impl fmt::Display for DrawReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrawReason::Stalemate => write!(f, "stalemate"),
+            DrawReason::Agreement => write!(f, "agreement"),
+            DrawReason::InsufficientMaterial => write!(f, "insufficient material"),
+            DrawReason::ThreefoldRepetition => write!(f, "threefold repetition"),
+            DrawReason::FivefoldRepetition => write!(f, "fivefold repetition"),
+            DrawReason::FiftyMoveRule => write!(f, "the fifty-move rule"),
+            DrawReason::SeventyFiveMoveRule => write!(f, "the seventy-five-move rule"),
+            DrawReason::TimeoutVsInsufficientMaterial => {
+                write!(f, "timeout vs insufficient material")
+            }
+        }
+    }
 }
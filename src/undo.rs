@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{Board, RedrawBoard},
+    history::MoveHistory,
+    network::NetworkConnection,
+    piece::LocalPlayer,
+    promotion::PendingPromotion,
+    rules::Move,
+    state::{game_over, GameState},
+};
+
+pub struct UndoPlugin;
+
+/// Moves taken back with `Ctrl+Z` in a local game, most recent last, which
+/// `Ctrl+Y` or `Ctrl+Shift+Z` play again until another move is made.
+#[derive(Resource, Debug, Default)]
+pub struct RedoStack {
+    moves: Vec<Move>,
+    /// Length of the game when the stack was last used, telling whether a
+    /// move has been played since.
+    plies: usize,
+}
+
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RedoStack>()
+            .add_systems(OnEnter(GameState::GameInitResources), clear_redo_stack)
+            .add_systems(
+                Update,
+                undo_redo.run_if(
+                    in_state(GameState::InGame)
+                        .or_else(game_over)
+                        .and_then(not(resource_exists::<NetworkConnection>))
+                        .and_then(not(resource_exists::<PendingPromotion>)),
+                ),
+            );
+    }
+}
+
+fn clear_redo_stack(mut redo_stack: ResMut<RedoStack>) {
+    *redo_stack = RedoStack::default();
+}
+
+/// Takes back or replays a move on `Ctrl+Z` and `Ctrl+Y`, picking the game
+/// up again if it had ended on the board. Games ended by resignation, an
+/// agreed or claimed draw or the clock stay over.
+#[allow(clippy::too_many_arguments)]
+fn undo_redo(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut board: ResMut<Board>,
+    mut history: ResMut<MoveHistory>,
+    mut redo_stack: ResMut<RedoStack>,
+    local_player: Res<LocalPlayer>,
+    mut redraw_events: EventWriter<RedrawBoard>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let control = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !control {
+        return;
+    }
+    if let GameState::GameOver(result) = *state.get() {
+        if board.game_result() != Some(result) {
+            return;
+        }
+    }
+
+    let undo = keyboard_input.just_pressed(KeyCode::KeyZ) && !shift;
+    let redo = keyboard_input.just_pressed(KeyCode::KeyY)
+        || (keyboard_input.just_pressed(KeyCode::KeyZ) && shift);
+
+    if history.moves.len() != redo_stack.plies {
+        redo_stack.moves.clear();
+    }
+
+    // Against the computer, step over its moves back to a position where
+    // the player is to move.
+    let mut changed = false;
+    loop {
+        if undo {
+            let Some(mv) = board.unmake_move() else {
+                break;
+            };
+            history.moves.pop();
+            redo_stack.moves.push(mv);
+        } else if redo {
+            let Some(mv) = redo_stack.moves.pop() else {
+                break;
+            };
+            board.make_move(mv);
+            history.moves.push(mv);
+        } else {
+            break;
+        }
+        changed = true;
+
+        if local_player.controls(board.side_to_move) {
+            break;
+        }
+    }
+    if !changed {
+        return;
+    }
+
+    redo_stack.plies = history.moves.len();
+    redraw_events.send(RedrawBoard);
+
+    let next = match board.game_result() {
+        Some(result) => GameState::GameOver(result),
+        None => GameState::InGame,
+    };
+    if *state.get() != next {
+        next_state.set(next);
+    }
+}
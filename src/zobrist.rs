@@ -0,0 +1,164 @@
+use crate::{
+    bitboard::{color_index, piece_type_index, square_index},
+    board::{Board, CastlingRights},
+    piece::{Piece, PieceColor},
+    rules::Square,
+};
+
+/// Random keys XORed together into the hash of a position, one for each
+/// piece on each square and one for each other part of the position. They
+/// are generated at compile time from a fixed seed so hashes stay the same
+/// from one run, and one build, to the next.
+struct ZobristKeys {
+    pieces: [[u64; 64]; 12],
+    black_to_move: u64,
+    castling: [u64; 4],
+    en_passant: [u64; 8],
+}
+
+static KEYS: ZobristKeys = ZobristKeys::generate(0x9e37_79b9_7f4a_7c15);
+
+/// Next output of the SplitMix64 generator, which is all const-friendly.
+const fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl ZobristKeys {
+    const fn generate(seed: u64) -> Self {
+        let mut state = seed;
+
+        let mut pieces = [[0; 64]; 12];
+        let mut piece = 0;
+        while piece < 12 {
+            let mut square = 0;
+            while square < 64 {
+                pieces[piece][square] = split_mix(&mut state);
+                square += 1;
+            }
+            piece += 1;
+        }
+
+        let black_to_move = split_mix(&mut state);
+
+        let mut castling = [0; 4];
+        let mut i = 0;
+        while i < 4 {
+            castling[i] = split_mix(&mut state);
+            i += 1;
+        }
+
+        let mut en_passant = [0; 8];
+        let mut i = 0;
+        while i < 8 {
+            en_passant[i] = split_mix(&mut state);
+            i += 1;
+        }
+
+        Self {
+            pieces,
+            black_to_move,
+            castling,
+            en_passant,
+        }
+    }
+}
+
+/// Key of `piece` standing on `square`.
+pub fn piece_key(piece: Piece, square: Square) -> u64 {
+    let piece_index = color_index(piece.color) * 6 + piece_type_index(piece.piece_type);
+    KEYS.pieces[piece_index][square_index(square)]
+}
+
+/// Key of the castling rights still held.
+pub fn castling_key(rights: CastlingRights) -> u64 {
+    [
+        rights.white_king_side,
+        rights.white_queen_side,
+        rights.black_king_side,
+        rights.black_queen_side,
+    ]
+    .into_iter()
+    .zip(KEYS.castling)
+    .filter(|&(held, _)| held)
+    .fold(0, |hash, (_, key)| hash ^ key)
+}
+
+impl Board {
+    /// Key of everything in the position besides the pieces: the side to
+    /// move, castling rights and the en passant file, counted only when a
+    /// pawn is there to take so that positions which play the same compare
+    /// equal.
+    pub fn state_key(&self) -> u64 {
+        let mut hash = castling_key(self.castling_rights);
+
+        if self.side_to_move == PieceColor::Black {
+            hash ^= KEYS.black_to_move;
+        }
+        if let Some(target) = self.en_passant.filter(|_| self.en_passant_available()) {
+            hash ^= KEYS.en_passant[target.x];
+        }
+
+        hash
+    }
+
+    /// Hashes the whole position from scratch. `Board::hash` holds the same
+    /// value, kept up to date move by move.
+    pub fn zobrist_hash(&self) -> u64 {
+        let mut hash = self.state_key();
+
+        for (y, row) in self.pieces.iter().enumerate() {
+            for (x, piece) in row.iter().enumerate() {
+                if let Some(piece) = piece {
+                    hash ^= piece_key(*piece, Square::new(x, y));
+                }
+            }
+        }
+
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{board::Board, rules::Move};
+
+    /// Plays `moves` on `board`, checking the incremental hash after each.
+    fn play(board: &mut Board, moves: &[&str]) {
+        for uci in moves {
+            let mv = Move::from_uci(uci).unwrap();
+            assert!(board.is_legal_move(mv), "{} is illegal", uci);
+            board.make_move(mv);
+            assert_eq!(
+                board.hash,
+                board.zobrist_hash(),
+                "hash out of date after {}",
+                uci
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_a_full_rehash() {
+        let mut board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        assert_eq!(board.hash, board.zobrist_hash());
+        play(
+            &mut board,
+            &["e5d6", "e8g8", "b7a8q", "g8g7", "e1c1", "f8f1", "d1f1"],
+        );
+    }
+
+    #[test]
+    fn transpositions_hash_the_same() {
+        let mut knights_first = Board::default();
+        play(&mut knights_first, &["g1f3", "g8f6", "b1c3"]);
+        let mut queen_side_first = Board::default();
+        play(&mut queen_side_first, &["b1c3", "g8f6", "g1f3"]);
+
+        assert_eq!(knights_first.hash, queen_side_first.hash);
+        assert_ne!(knights_first.hash, Board::default().hash);
+    }
+}